@@ -3,11 +3,80 @@ extern crate criterion;
 
 use criterion::Criterion;
 
-use futures::{SinkExt, StreamExt};
-use std::thread::spawn;
-use tmq::{pull, push, SocketExt};
+use futures::{Sink, SinkExt, StreamExt};
+use std::thread::{spawn, JoinHandle};
+use tmq::{pull, push, AsZmqSocket, Multipart, SocketExt, TmqError};
 use zmq::SocketType;
 
+const BATCH_SIZE: usize = 1000;
+
+/// Receives multiparts until the number announced by a single-frame multipart was reached.
+fn counting_receiver(address: &'static str) -> JoinHandle<()> {
+    spawn(move || {
+        let ctx = zmq::Context::new();
+        let receiver = ctx.socket(SocketType::PULL).unwrap();
+        receiver.bind(address).unwrap();
+
+        let mut counter = 0;
+        let mut target = -1;
+        loop {
+            let msg = receiver.recv_multipart(0).unwrap();
+            if msg.len() == 1 {
+                target = std::str::from_utf8(&msg[0]).unwrap().parse().unwrap();
+            } else {
+                counter += 1;
+            }
+            if counter == target {
+                break;
+            }
+        }
+    })
+}
+
+/// Sends batches of `BATCH_SIZE` multiparts with `send_all` through the socket created by
+/// `make_socket`.
+fn bench_send_batch<S, F>(c: &mut Criterion, name: &str, address: &'static str, mut make_socket: F)
+where
+    S: Sink<Multipart, Error = TmqError> + AsZmqSocket + Unpin,
+    F: FnMut() -> S,
+{
+    c.bench_function(name, |b| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let thread = counting_receiver(address);
+
+        let mut socket = {
+            let _guard = runtime.enter();
+            make_socket()
+        };
+        socket.get_socket().set_linger(0).unwrap();
+
+        let mut sent = 0;
+        b.iter_with_setup(
+            || {
+                futures::stream::iter((0..BATCH_SIZE).map(|_| {
+                    Ok(Multipart::from(vec![
+                        zmq::Message::from("hello"),
+                        zmq::Message::from("world"),
+                    ]))
+                }))
+            },
+            |mut batch| {
+                runtime.block_on(socket.send_all(&mut batch)).unwrap();
+                sent += BATCH_SIZE;
+            },
+        );
+
+        runtime
+            .block_on(socket.send(Multipart::from(vec![zmq::Message::from(&sent.to_string())])))
+            .unwrap();
+        thread.join().unwrap();
+    });
+}
+
 fn poll_benchmark(c: &mut Criterion) {
     c.bench_function("receive", |b| {
         let address = "tcp://127.0.0.1:3011";
@@ -31,7 +100,7 @@ fn poll_benchmark(c: &mut Criterion) {
             || {
                 sender
                     .send_multipart(
-                        vec![zmq::Message::from("hello"), zmq::Message::from("world")].into_iter(),
+                        vec![zmq::Message::from("hello"), zmq::Message::from("world")],
                         0,
                     )
                     .unwrap();
@@ -49,25 +118,7 @@ fn poll_benchmark(c: &mut Criterion) {
             .build()
             .unwrap();
 
-        let thread = spawn(move || {
-            let ctx = zmq::Context::new();
-            let receiver = ctx.socket(SocketType::PULL).unwrap();
-            receiver.bind(address).unwrap();
-
-            let mut counter = 0;
-            let mut target = -1;
-            loop {
-                let msg = receiver.recv_multipart(0).unwrap();
-                if msg.len() == 1 {
-                    target = std::str::from_utf8(&msg[0]).unwrap().parse().unwrap();
-                } else {
-                    counter += 1;
-                }
-                if counter == target {
-                    break;
-                }
-            }
-        });
+        let thread = counting_receiver(address);
 
         let ctx = zmq::Context::new();
         let mut socket = {
@@ -90,6 +141,17 @@ fn poll_benchmark(c: &mut Criterion) {
             .unwrap();
         thread.join().unwrap();
     });
+
+    let ctx = zmq::Context::new();
+    bench_send_batch(c, "send_all", "tcp://127.0.0.1:3012", || {
+        push(&ctx).connect("tcp://127.0.0.1:3012").unwrap()
+    });
+    bench_send_batch(c, "send_all_buffered", "tcp://127.0.0.1:3013", || {
+        push(&ctx)
+            .connect("tcp://127.0.0.1:3013")
+            .unwrap()
+            .buffered(BATCH_SIZE)
+    });
 }
 
 criterion_group!(benches, poll_benchmark);
@@ -1,22 +1,26 @@
 //! This example demonstrates asynchronous distribution of requests given by a set of clients
-//! among a set of workers. The clients communicate with a ROUTER broker, which load balances their
-//! requests among the workers.
+//! among a set of workers. The clients communicate with a Paranoid Pirate queue, which load
+//! balances their requests among the workers.
 //!
-//! Each client continuously creates a request and sends it to the proxy.
-//! The proxy forwards it to a worker. Since the proxy is a ROUTER socket, it will use the identity
-//! of the client socket as the first message in the multipart sent to the worker. The worker will
-//! simulate some amount of work and respond with the client identity followed by some payload. The
-//! proxy will then use the identity to respond to the correct client.
+//! Each client continuously creates a request and sends it to the queue.
+//! The queue forwards it to the least recently used ready worker. Since the frontend of the queue
+//! is a ROUTER socket, the identity of the client socket is the envelope of the request, which the
+//! worker keeps for its reply. The worker will simulate some amount of work and reply with some
+//! payload. The queue will then use the envelope to respond to the correct client.
 //!
-//! All clients, workers and the proxy run on the same thread.
+//! The queue and the workers exchange heartbeats, so the queue stops sending requests to workers
+//! which have died and the workers reconnect when the queue goes away.
 //!
-//! The proxy is implemented using asynchronous sockets as an example of using `futures::select`.
-//! A more performant solution would be to use `zmq::proxy`, which is designed for this usage.
+//! All clients, workers and the queue run on the same thread.
 
-use futures::{future, SinkExt, StreamExt};
+use futures::{SinkExt, StreamExt};
 use rand::Rng;
 use std::{error::Error, rc::Rc, time::Duration};
-use tmq::{dealer, router, Context, Multipart};
+use tmq::{
+    dealer,
+    patterns::paranoid_pirate::{PirateQueue, PirateWorker},
+    Context, Multipart,
+};
 use tokio::time::sleep;
 
 async fn client(ctx: Rc<Context>, client_id: u64, frontend: String) -> tmq::Result<()> {
@@ -43,15 +47,14 @@ async fn client(ctx: Rc<Context>, client_id: u64, frontend: String) -> tmq::Resu
     }
 }
 async fn worker(ctx: Rc<Context>, worker_id: u64, backend: String) -> Result<(), Box<dyn Error>> {
-    let mut sock = dealer(&ctx).connect(&backend)?;
+    let mut worker = PirateWorker::connect(&*ctx, &backend)?;
     let mut rng = rand::thread_rng();
 
-    loop {
-        let mut request = sock.next().await.unwrap()?;
-        let identity = request.pop_front().unwrap();
-        let client_id = request.pop_front().unwrap();
-        let request_id = request.pop_front().unwrap();
-        let request_body = request.pop_front().unwrap();
+    while let Some(request) = worker.next().await {
+        let mut request = request?;
+        let client_id = request.body_mut().pop_front().unwrap();
+        let request_id = request.body_mut().pop_front().unwrap();
+        let request_body = request.body_mut().pop_front().unwrap();
 
         println!(
             "Worker {} handling request(id={} body={}) from client {}",
@@ -65,36 +68,14 @@ async fn worker(ctx: Rc<Context>, worker_id: u64, backend: String) -> Result<(),
         let sleep_time = rng.gen_range(100..3000);
         sleep(Duration::from_millis(sleep_time)).await;
 
-        let response = vec![identity, client_id, request_id, "response".into()];
-        sock.send(response).await?;
+        request.reply(vec![client_id, request_id, "response".into()])?;
     }
+    Ok(())
 }
 
-/// Simulates zmq::proxy using asynchronous sockets.
-async fn proxy(ctx: Rc<Context>, frontend: String, backend: String) -> tmq::Result<()> {
-    let (mut router_tx, mut router_rx) = router(&ctx).bind(&frontend)?.split();
-    let (mut dealer_tx, mut dealer_rx) = dealer(&ctx).bind(&backend)?.split();
-
-    let mut frontend_fut = router_rx.next();
-    let mut backend_fut = dealer_rx.next();
-
-    loop {
-        let msg = future::select(frontend_fut, backend_fut).await;
-        match msg {
-            future::Either::Left(router_msg) => {
-                // proxy received a message from a client
-                dealer_tx.send(router_msg.0.unwrap()?).await?;
-                frontend_fut = router_rx.next();
-                backend_fut = router_msg.1;
-            }
-            future::Either::Right(dealer_msg) => {
-                // proxy received a message from a worker
-                router_tx.send(dealer_msg.0.unwrap()?).await?;
-                backend_fut = dealer_rx.next();
-                frontend_fut = dealer_msg.1;
-            }
-        }
-    }
+/// Load balances the requests of the clients among the workers.
+async fn queue(ctx: Rc<Context>, frontend: String, backend: String) -> tmq::Result<()> {
+    PirateQueue::bind(&*ctx, &frontend, &backend)?.run().await
 }
 
 fn main() -> tmq::Result<()> {
@@ -102,7 +83,7 @@ fn main() -> tmq::Result<()> {
     let backend = "tcp://127.0.0.1:5556".to_string();
     let ctx = Rc::new(Context::new());
 
-    let mut runtime = tokio::runtime::Builder::new_current_thread()
+    let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
     let tasks = tokio::task::LocalSet::new();
@@ -129,10 +110,10 @@ fn main() -> tmq::Result<()> {
         });
     }
 
-    tasks.block_on(&mut runtime, async move {
-        proxy(ctx.clone(), frontend, backend)
+    tasks.block_on(&runtime, async move {
+        queue(ctx.clone(), frontend, backend)
             .await
-            .expect("Proxy failed");
+            .expect("Queue failed");
     });
 
     Ok(())
@@ -7,7 +7,7 @@ use tmq::{pull, Context, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    if let Err(_) = env::var("RUST_LOG") {
+    if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "subscribe=DEBUG");
     }
 
@@ -10,7 +10,7 @@ use tmq::{push, Context, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    if let Err(_) = env::var("RUST_LOG") {
+    if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "subscribe=DEBUG");
     }
 
@@ -0,0 +1,243 @@
+//! Typed sockets which convert between multiparts and Rust values.
+//!
+//! A [`Codec`] describes how values are turned into multiparts and back.
+//! [`Typed`] wraps any tmq socket and uses a codec to make it a `Sink` and/or `Stream` of values.
+//!
+//! Codecs based on `serde` are available behind cargo features:
+//!
+//! * `json`: [`JsonCodec`]
+//! * `bincode`: [`BincodeCodec`]
+//! * `msgpack`: [`MsgPackCodec`]
+//!
+//! The `serde` codecs encode each value into a single frame.
+//!
+//! ## Usage Example
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "json")]
+//! # mod example {
+//! use futures::SinkExt;
+//! use serde::{Deserialize, Serialize};
+//! use tmq::{codec::{JsonCodec, Typed}, push, Context, Result};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Job {
+//!     id: u64,
+//! }
+//!
+//! #[tokio::main]
+//! # pub
+//! async fn main() -> Result<()> {
+//!     let socket = push(&Context::new()).connect("tcp://127.0.0.1:7899")?;
+//!     let mut jobs = Typed::new(socket, JsonCodec::<Job>::new());
+//!     jobs.send(Job { id: 1 }).await
+//! }
+//! # }
+//! # #[cfg(feature = "json")]
+//! # fn main() -> tmq::Result<()> {
+//! #     example::main()
+//! # }
+//! # #[cfg(not(feature = "json"))]
+//! # fn main() {}
+//! ```
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Sink, Stream};
+
+use crate::{Multipart, Result, TmqError};
+
+/// Converts values into multiparts and back.
+pub trait Codec {
+    /// Type of the encoded and decoded values.
+    type Item;
+
+    /// Encodes a value into a multipart.
+    fn encode(&mut self, item: Self::Item) -> Result<Multipart>;
+
+    /// Decodes a value from a multipart.
+    ///
+    /// Malformed messages should be reported with [`TmqError::Codec`].
+    fn decode(&mut self, multipart: Multipart) -> Result<Self::Item>;
+}
+
+/// Socket wrapper which sends and receives values encoded by a [`Codec`].
+///
+/// If the inner socket is a `Sink` of multiparts, `Typed` is a `Sink` of `C::Item`.
+/// If the inner socket is a `Stream` of multiparts, `Typed` is a `Stream` of `Result<C::Item>`.
+/// Messages which can't be decoded are returned as [`TmqError::Codec`] errors, the stream
+/// continues afterwards.
+pub struct Typed<S, C> {
+    inner: S,
+    codec: C,
+}
+
+impl<S, C> Typed<S, C> {
+    /// Wraps `socket`, converting messages with `codec`.
+    pub fn new(socket: S, codec: C) -> Self {
+        Self {
+            inner: socket,
+            codec,
+        }
+    }
+
+    /// Returns a reference to the inner socket.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner socket.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns the inner socket and the codec.
+    pub fn into_parts(self) -> (S, C) {
+        (self.inner, self.codec)
+    }
+}
+
+impl<S: crate::AsZmqSocket, C> crate::AsZmqSocket for Typed<S, C> {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.inner.get_socket()
+    }
+}
+
+impl<S, C> Sink<C::Item> for Typed<S, C>
+where
+    S: Sink<Multipart, Error = TmqError> + Unpin,
+    C: Codec + Unpin,
+{
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: C::Item) -> Result<()> {
+        let multipart = self.codec.encode(item)?;
+        Pin::new(&mut self.inner).start_send(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+impl<S, C> Stream for Typed<S, C>
+where
+    S: Stream<Item = Result<Multipart>> + Unpin,
+    C: Codec + Unpin,
+{
+    type Item = Result<C::Item>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self { inner, codec } = &mut *self;
+        let multipart = ready!(Pin::new(inner).poll_next(cx));
+        Poll::Ready(multipart.map(|multipart| multipart.and_then(|m| codec.decode(m))))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn single_frame(multipart: &Multipart) -> Result<&[u8]> {
+    match multipart.len() {
+        1 => Ok(&multipart[0]),
+        len => Err(TmqError::Codec(
+            format!("Expected a single frame, got {}", len).into(),
+        )),
+    }
+}
+
+#[cfg(feature = "serde")]
+macro_rules! serde_codec {
+    ($name: ident, $feature: literal, $format: literal, $encode: expr, $decode: expr) => {
+        #[doc = concat!("Codec encoding values as ", $format, " into a single frame.")]
+        ///
+        #[doc = concat!("Requires the `", $feature, "` feature.")]
+        pub struct $name<T> {
+            _item: ::std::marker::PhantomData<fn(T) -> T>,
+        }
+
+        impl<T> $name<T> {
+            /// Creates a new codec.
+            pub fn new() -> Self {
+                Self {
+                    _item: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        impl<T> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<T> Clone for $name<T> {
+            fn clone(&self) -> Self {
+                Self::new()
+            }
+        }
+
+        impl<T> ::std::fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+
+        impl<T> Codec for $name<T>
+        where
+            T: ::serde::Serialize + ::serde::de::DeserializeOwned,
+        {
+            type Item = T;
+
+            fn encode(&mut self, item: T) -> Result<Multipart> {
+                let bytes = $encode(&item).map_err(|e| TmqError::Codec(Box::new(e)))?;
+                Ok(zmq::Message::from(bytes).into())
+            }
+
+            fn decode(&mut self, multipart: Multipart) -> Result<T> {
+                $decode(single_frame(&multipart)?).map_err(|e| TmqError::Codec(Box::new(e)))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+serde_codec!(
+    JsonCodec,
+    "json",
+    "JSON",
+    serde_json::to_vec,
+    serde_json::from_slice
+);
+
+#[cfg(feature = "bincode")]
+serde_codec!(
+    BincodeCodec,
+    "bincode",
+    "bincode",
+    bincode::serialize,
+    bincode::deserialize
+);
+
+#[cfg(feature = "msgpack")]
+serde_codec!(
+    MsgPackCodec,
+    "msgpack",
+    "MessagePack",
+    rmp_serde::to_vec_named,
+    rmp_serde::from_slice
+);
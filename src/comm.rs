@@ -1,6 +1,20 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, future::poll_fn, ready, Sink, SinkExt, Stream, StreamExt};
+use tokio::sync::watch;
+
 use crate::{
+    message::{into_frames, Frames},
     poll::{ReceiverBuffer, ZmqPoller},
-    Multipart,
+    socket::AsZmqSocket,
+    timeout::default_timeout,
+    Multipart, Result, SinkItem, SocketTimeoutExt, TmqError,
 };
 
 type Poller = ZmqPoller;
@@ -8,7 +22,7 @@ type Poller = ZmqPoller;
 /// Sends multiparts using an owned Poller.
 pub struct Sender {
     pub(crate) poller: Poller,
-    pub(crate) buffer: Multipart,
+    pub(crate) buffer: Frames,
 }
 impl_as_socket!(Sender, poller);
 impl_sink!(Sender, buffer, poller);
@@ -20,21 +34,218 @@ impl Sender {
             buffer: Default::default(),
         }
     }
+
+    /// Turns the sender into a [`BufferedSender`] which queues up to `capacity` multiparts.
+    ///
+    /// Queued multiparts are only written to the socket when the sink is flushed, or when
+    /// another one is sent while the queue is full. Then the whole queue is written at once.
+    /// `SinkExt::send` flushes after every multipart, so use `SinkExt::feed` or
+    /// `SinkExt::send_all` to make use of the queue, and flush the sink afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn buffered(self, capacity: usize) -> BufferedSender {
+        BufferedSender::new(self, capacity)
+    }
+
+    /// Spawns a task driving this sender and returns a [`SenderHandle`] to it.
+    pub(crate) fn into_handle(self) -> SenderHandle {
+        let send_timeout = default_timeout(self.poller.get_socket().get_sndtimeo());
+        let (queue, receiver) = mpsc::channel(HANDLE_QUEUE_SIZE);
+        let (stopped, done) = watch::channel(());
+        tokio::spawn(drive_sender(self, receiver, stopped));
+        SenderHandle {
+            queue,
+            done,
+            send_timeout,
+        }
+    }
+}
+
+/// Sends buffered multiparts using an owned Poller.
+///
+/// Up to `capacity` multiparts are queued by the sink without touching the socket. They are
+/// written in a single burst when the queue is full or the sink is flushed.
+pub struct BufferedSender {
+    pub(crate) poller: Poller,
+    pub(crate) queue: VecDeque<Frames>,
+    pub(crate) capacity: usize,
+}
+impl_as_socket!(BufferedSender, poller);
+
+impl BufferedSender {
+    fn new(sender: Sender, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        let mut queue = VecDeque::with_capacity(capacity);
+        // A partially sent multipart is finished before the queued ones.
+        if !sender.buffer.is_empty() {
+            queue.push_back(sender.buffer);
+        }
+        Self {
+            poller: sender.poller,
+            queue,
+            capacity,
+        }
+    }
+}
+
+impl<T: SinkItem> Sink<T> for BufferedSender {
+    type Error = TmqError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self {
+            poller,
+            queue,
+            capacity,
+        } = self.get_mut();
+        if queue.len() >= *capacity {
+            ready!(poller.multipart_flush_queue(cx, queue))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<()> {
+        self.queue.push_back(into_frames(item));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self { poller, queue, .. } = self.get_mut();
+        poller.multipart_flush_queue(cx, queue)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<T>::poll_flush(self, cx)
+    }
+}
+
+const HANDLE_QUEUE_SIZE: usize = 128;
+
+enum HandleCommand {
+    Send(Frames),
+    Shutdown,
+}
+
+/// Cloneable handle for sending multiparts through a socket owned by a driver task.
+///
+/// Created by `into_handle` on sending-only sockets. The handle is `Send + Sync` and cheap to
+/// clone, so many tasks can send through the same socket. Messages are queued in a bounded
+/// channel, sending waits while the queue is full.
+///
+/// The socket is closed once every handle is dropped or [`shutdown`](#method.shutdown) is called,
+/// after all queued messages have been sent.
+#[derive(Clone)]
+pub struct SenderHandle {
+    queue: mpsc::Sender<HandleCommand>,
+    done: watch::Receiver<()>,
+    send_timeout: Option<Duration>,
+}
+
+impl SenderHandle {
+    /// Stops accepting messages, sends the messages which are already queued and closes the
+    /// socket.
+    ///
+    /// Sending through other clones of the handle fails with [`TmqError::Closed`] afterwards.
+    pub async fn shutdown(mut self) {
+        // If the queue is already closed, the driver is stopping anyway.
+        let _ = self.queue.send(HandleCommand::Shutdown).await;
+        while self.done.changed().await.is_ok() {}
+    }
+}
+
+impl SocketTimeoutExt for SenderHandle {
+    fn default_recv_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn default_send_timeout(&self) -> Option<Duration> {
+        self.send_timeout
+    }
+}
+
+impl<T: SinkItem> Sink<T> for SenderHandle {
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.queue.poll_ready(cx).map_err(|_| TmqError::Closed)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<()> {
+        self.queue
+            .start_send(HandleCommand::Send(into_frames(item)))
+            .map_err(|_| TmqError::Closed)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.queue)
+            .poll_flush(cx)
+            .map_err(|_| TmqError::Closed)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.queue)
+            .poll_close(cx)
+            .map_err(|_| TmqError::Closed)
+    }
+}
+
+async fn drive_sender(
+    mut socket: Sender,
+    mut queue: mpsc::Receiver<HandleCommand>,
+    _stopped: watch::Sender<()>,
+) {
+    // After the queue is closed, the remaining messages are still received.
+    while let Some(command) = queue.next().await {
+        match command {
+            HandleCommand::Send(mut frames) => {
+                let poller = &mut socket.poller;
+                if let Err(e) = poll_fn(move |cx| poller.multipart_flush(cx, &mut frames)).await {
+                    log::error!("Socket driver failed: {}", e);
+                    return;
+                }
+            }
+            HandleCommand::Shutdown => queue.close(),
+        }
+    }
 }
 
 /// Receives multiparts using an owned Poller.
 pub struct Receiver {
     pub(crate) poller: Poller,
+    pub(crate) read_buffer: Option<ReceiverBuffer>,
 }
 impl_as_socket!(Receiver, poller);
-impl_stream!(Receiver, poller);
+impl_stream!(Receiver, read_buffer, poller);
 
 impl Receiver {
     pub(crate) fn new(poller: Poller) -> Self {
-        Self { poller }
+        Self {
+            poller,
+            read_buffer: None,
+        }
     }
     pub fn buffered(self, capacity: usize) -> BufferedReceiver {
-        BufferedReceiver::new(self.poller, capacity)
+        let buffer = ReceiverBuffer::new(capacity).with_pending(self.read_buffer);
+        BufferedReceiver::new(self.poller, buffer)
+    }
+
+    /// Like [`buffered`](#method.buffered), but the batch size adapts to the observed burst
+    /// length, between `min` and `max` multiparts.
+    pub fn buffered_adaptive(self, min: usize, max: usize) -> BufferedReceiver {
+        let buffer = ReceiverBuffer::adaptive(min, max).with_pending(self.read_buffer);
+        BufferedReceiver::new(self.poller, buffer)
+    }
+
+    /// Receives through `buffer` from now on, while keeping the receiver itself.
+    pub(crate) fn set_read_buffer(&mut self, buffer: ReceiverBuffer) {
+        self.read_buffer = Some(buffer.with_pending(self.read_buffer.take()));
+    }
+
+    pub(crate) fn batch_size(&self) -> usize {
+        self.read_buffer
+            .as_ref()
+            .map_or(1, ReceiverBuffer::capacity)
     }
 }
 
@@ -47,28 +258,163 @@ impl_as_socket!(BufferedReceiver, poller);
 impl_buffered_stream!(BufferedReceiver, buffer, poller);
 
 impl BufferedReceiver {
-    pub(crate) fn new(poller: Poller, capacity: usize) -> Self {
-        Self {
-            poller,
-            buffer: ReceiverBuffer::new(capacity),
-        }
+    pub(crate) fn new(poller: Poller, buffer: ReceiverBuffer) -> Self {
+        Self { poller, buffer }
+    }
+
+    /// Returns the maximum number of multiparts which are currently read in one batch.
+    pub fn batch_size(&self) -> usize {
+        self.buffer.capacity()
     }
 }
 
 /// Sends ands receives multiparts using an owned Poller.
 pub struct SenderReceiver {
     pub(crate) poller: Poller,
-    pub(crate) buffer: Multipart,
+    pub(crate) buffer: Frames,
+    pub(crate) read_buffer: Option<ReceiverBuffer>,
 }
 impl_as_socket!(SenderReceiver, poller);
 impl_sink!(SenderReceiver, buffer, poller);
-impl_stream!(SenderReceiver, poller);
+impl_stream!(SenderReceiver, read_buffer, poller);
 
 impl SenderReceiver {
     pub(crate) fn new(poller: Poller) -> Self {
         Self {
             poller,
             buffer: Default::default(),
+            read_buffer: None,
         }
     }
 }
+
+impl SenderReceiver {
+    /// Receives through `buffer` from now on, while keeping the socket itself.
+    pub(crate) fn set_read_buffer(&mut self, buffer: ReceiverBuffer) {
+        self.read_buffer = Some(buffer.with_pending(self.read_buffer.take()));
+    }
+
+    pub(crate) fn batch_size(&self) -> usize {
+        self.read_buffer
+            .as_ref()
+            .map_or(1, ReceiverBuffer::capacity)
+    }
+
+    /// Splits the socket into a [`WriteHalf`] and a [`ReadHalf`] which can be used from
+    /// different tasks.
+    ///
+    /// The halves are returned in the same order as `StreamExt::split`. A buffered socket
+    /// keeps receiving in batches through the [`ReadHalf`].
+    pub fn into_split(self) -> (WriteHalf, ReadHalf) {
+        let shared = Arc::new(Shared {
+            poller: Mutex::new(self.poller),
+        });
+        (
+            WriteHalf {
+                shared: shared.clone(),
+                buffer: self.buffer,
+            },
+            ReadHalf {
+                shared,
+                read_buffer: self.read_buffer,
+            },
+        )
+    }
+}
+
+struct Shared {
+    // ZMQ sockets are not thread safe, so every operation on the socket takes the lock.
+    // Operations never block, so the lock is only held briefly.
+    poller: Mutex<Poller>,
+}
+
+impl Shared {
+    fn poller(&self) -> MutexGuard<'_, Poller> {
+        self.poller.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn recv_timeout(&self) -> Option<Duration> {
+        default_timeout(self.poller().get_socket().get_rcvtimeo())
+    }
+
+    fn send_timeout(&self) -> Option<Duration> {
+        default_timeout(self.poller().get_socket().get_sndtimeo())
+    }
+}
+
+/// Receiving half of a split socket, see e.g.
+/// [`Dealer::into_split`](crate::dealer::Dealer::into_split).
+pub struct ReadHalf {
+    shared: Arc<Shared>,
+    read_buffer: Option<ReceiverBuffer>,
+}
+
+impl SocketTimeoutExt for ReadHalf {
+    fn default_recv_timeout(&self) -> Option<Duration> {
+        self.shared.recv_timeout()
+    }
+
+    fn default_send_timeout(&self) -> Option<Duration> {
+        self.shared.send_timeout()
+    }
+}
+
+impl Stream for ReadHalf {
+    type Item = Result<Multipart>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let poller = this.shared.poller();
+        poller
+            .multipart_recv_with(cx, &mut this.read_buffer)
+            .map(Some)
+    }
+}
+
+/// Sending half of a split socket, see e.g.
+/// [`Dealer::into_split`](crate::dealer::Dealer::into_split).
+pub struct WriteHalf {
+    shared: Arc<Shared>,
+    buffer: Frames,
+}
+
+impl WriteHalf {
+    fn poll_flush_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.shared.poller().multipart_flush(cx, &mut self.buffer)
+    }
+}
+
+impl SocketTimeoutExt for WriteHalf {
+    fn default_recv_timeout(&self) -> Option<Duration> {
+        self.shared.recv_timeout()
+    }
+
+    fn default_send_timeout(&self) -> Option<Duration> {
+        self.shared.send_timeout()
+    }
+}
+
+// Like the `SplitSink` of `StreamExt::split`, this only accepts `Multipart`s, so that
+// `sink.send(x.into())` keeps inferring the item type.
+impl Sink<Multipart> for WriteHalf {
+    type Error = TmqError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.get_mut().poll_flush_buffer(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Multipart) -> Result<()> {
+        assert!(self.buffer.is_empty());
+        self.buffer = item.into();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush_buffer(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush_buffer(cx)
+    }
+}
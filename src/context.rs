@@ -0,0 +1,433 @@
+use std::{
+    collections::BTreeMap,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    os::raw::{c_int, c_void},
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use zmq::SocketType;
+
+use crate::Result;
+
+/// ZMQ context which can be shut down without blocking the runtime.
+///
+/// Dropping the last handle of a `zmq::Context` blocks the current thread until every socket of
+/// the context has been closed and their linger periods have expired. [`shutdown`] runs this on
+/// a blocking thread instead.
+///
+/// The context keeps track of the sockets which have been created from it by tmq, see
+/// [`open_sockets`]. Every socket keeps the context alive, so the last handle of a context is
+/// dropped together with its last socket. When that happens within a Tokio runtime, the context
+/// is terminated on a blocking thread in the background, otherwise on the current thread.
+///
+/// Anywhere tmq takes a context, a `zmq::Context` can be used as well.
+///
+/// ## Usage Example
+/// ```rust,no_run
+/// use tmq::{pull, Context, Result};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let ctx = Context::builder().set_io_threads(2).build()?;
+///     let socket = pull(&ctx).bind("tcp://127.0.0.1:7899")?;
+///
+///     drop(socket);
+///     ctx.shutdown().await
+/// }
+/// ```
+///
+/// [`shutdown`]: #method.shutdown
+/// [`open_sockets`]: #method.open_sockets
+#[derive(Clone)]
+pub struct Context {
+    raw: Arc<RawContext>,
+}
+
+impl Context {
+    /// Creates a context with the default options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if libzmq fails to create the context, e.g. because the process has run out of
+    /// file descriptors. The same applies to `Context::default()`. Use
+    /// `Context::builder().build()` (see [`ContextBuilder::build`]), which returns the error
+    /// instead.
+    pub fn new() -> Self {
+        match RawContext::new() {
+            Ok(raw) => Self { raw: Arc::new(raw) },
+            Err(e) => panic!("Failed to create ZMQ context: {}", e),
+        }
+    }
+
+    /// Creates a builder for a context with custom options.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::default()
+    }
+
+    /// Returns the sockets of this context which haven't been closed yet.
+    pub fn open_sockets(&self) -> Vec<SocketInfo> {
+        lock(&self.raw.sockets).values().cloned().collect()
+    }
+
+    /// Terminates the context on a blocking thread.
+    ///
+    /// Sockets can't be created from the context (or its clones) anymore once the shutdown has
+    /// started. The returned future completes after all sockets of the context have been closed
+    /// and their pending messages have been sent or their linger periods have expired.
+    /// If there are open sockets, they are logged while waiting for them.
+    pub async fn shutdown(self) -> Result<()> {
+        let open = self.open_sockets();
+        if !open.is_empty() {
+            log::warn!(
+                "Waiting for {} open sockets to close: {:?}",
+                open.len(),
+                open
+            );
+        }
+
+        let raw = self.raw;
+        tokio::task::spawn_blocking(move || raw.term())
+            .await
+            .map_err(std::io::Error::from)?
+    }
+
+    pub(crate) fn socket(&self, socket_type: SocketType) -> Result<OwnedSocket> {
+        let ptr = {
+            let ctx = lock(&self.raw.ctx);
+            match *ctx {
+                Some(RawPtr(ctx)) => unsafe {
+                    zmq_sys::zmq_socket(ctx, raw_socket_type(socket_type))
+                },
+                None => return Err(zmq::Error::ETERM.into()),
+            }
+        };
+        if ptr.is_null() {
+            return Err(last_error().into());
+        }
+
+        lock(&self.raw.sockets).insert(ptr as usize, SocketInfo::new(socket_type));
+
+        // SAFETY: the pointer is a freshly created socket, which is owned by the returned value.
+        let socket = unsafe { zmq::Socket::from_raw(ptr) };
+        Ok(OwnedSocket::new(socket, Some(self.raw.clone())))
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder of a [`Context`] with custom options.
+///
+/// See ZMQ documentation for more info on what these options do: [http://api.zeromq.org/4-3:zmq-ctx-set](http://api.zeromq.org/4-3:zmq-ctx-set)
+#[derive(Default)]
+pub struct ContextBuilder {
+    options: Vec<(u32, i32)>,
+}
+
+impl ContextBuilder {
+    fn option(mut self, option: u32, value: i32) -> Self {
+        self.options.push((option, value));
+        self
+    }
+
+    /// Setter for the `ZMQ_IO_THREADS` option.
+    pub fn set_io_threads(self, value: i32) -> Self {
+        self.option(zmq_sys::ZMQ_IO_THREADS, value)
+    }
+
+    /// Setter for the `ZMQ_MAX_SOCKETS` option.
+    pub fn set_max_sockets(self, value: i32) -> Self {
+        self.option(zmq_sys::ZMQ_MAX_SOCKETS, value)
+    }
+
+    /// Setter for the `ZMQ_IPV6` option, the default of the sockets' `ZMQ_IPV6` option.
+    pub fn set_ipv6(self, value: bool) -> Self {
+        self.option(zmq_sys::ZMQ_IPV6, value as i32)
+    }
+
+    /// Setter for the `ZMQ_THREAD_PRIORITY` option.
+    pub fn set_thread_priority(self, value: i32) -> Self {
+        self.option(zmq_sys::ZMQ_THREAD_PRIORITY, value)
+    }
+
+    /// Setter for the `ZMQ_THREAD_SCHED_POLICY` option.
+    pub fn set_thread_sched_policy(self, value: i32) -> Self {
+        self.option(zmq_sys::ZMQ_THREAD_SCHED_POLICY, value)
+    }
+
+    /// Adds a CPU to the affinity of the I/O threads (`ZMQ_THREAD_AFFINITY_CPU_ADD`).
+    pub fn add_thread_affinity_cpu(self, cpu: i32) -> Self {
+        self.option(zmq_sys::ZMQ_THREAD_AFFINITY_CPU_ADD, cpu)
+    }
+
+    /// Creates the context with the configured options.
+    pub fn build(self) -> Result<Context> {
+        let raw = RawContext::new()?;
+        {
+            let RawPtr(ctx) = lock(&raw.ctx).unwrap();
+            for (option, value) in self.options {
+                if unsafe { zmq_sys::zmq_ctx_set(ctx, option as c_int, value) } == -1 {
+                    return Err(last_error().into());
+                }
+            }
+        }
+        Ok(Context { raw: Arc::new(raw) })
+    }
+}
+
+/// A socket which has been created from a [`Context`] and hasn't been closed yet.
+#[derive(Clone, Debug)]
+pub struct SocketInfo {
+    socket_type: SocketType,
+    endpoints: Vec<String>,
+}
+
+impl SocketInfo {
+    fn new(socket_type: SocketType) -> Self {
+        Self {
+            socket_type,
+            endpoints: vec![],
+        }
+    }
+
+    /// Returns the type of the socket.
+    pub fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    /// Returns the endpoints which the socket has been bound or connected to by its builder.
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+}
+
+/// Contexts which tmq sockets can be created from: [`Context`] and `zmq::Context`.
+pub trait AsContext: private::Sealed {}
+
+impl<T: private::Sealed + ?Sized> AsContext for T {}
+
+mod private {
+    /// The context used by a socket builder.
+    #[derive(Clone)]
+    pub enum ContextRef {
+        Tmq(super::Context),
+        Zmq(zmq::Context),
+    }
+
+    pub trait Sealed {
+        fn context_ref(&self) -> ContextRef;
+    }
+}
+
+pub(crate) use private::ContextRef;
+
+impl private::Sealed for Context {
+    fn context_ref(&self) -> ContextRef {
+        ContextRef::Tmq(self.clone())
+    }
+}
+
+impl private::Sealed for zmq::Context {
+    fn context_ref(&self) -> ContextRef {
+        ContextRef::Zmq(self.clone())
+    }
+}
+
+impl private::Sealed for ContextRef {
+    fn context_ref(&self) -> ContextRef {
+        self.clone()
+    }
+}
+
+impl<T: private::Sealed + ?Sized> private::Sealed for &T {
+    fn context_ref(&self) -> ContextRef {
+        (**self).context_ref()
+    }
+}
+
+impl<T: private::Sealed + ?Sized> private::Sealed for Rc<T> {
+    fn context_ref(&self) -> ContextRef {
+        (**self).context_ref()
+    }
+}
+
+impl<T: private::Sealed + ?Sized> private::Sealed for Arc<T> {
+    fn context_ref(&self) -> ContextRef {
+        (**self).context_ref()
+    }
+}
+
+impl ContextRef {
+    pub(crate) fn from_context<C: AsContext + ?Sized>(context: &C) -> Self {
+        context.context_ref()
+    }
+
+    pub(crate) fn socket(&self, socket_type: SocketType) -> Result<OwnedSocket> {
+        match self {
+            ContextRef::Tmq(context) => context.socket(socket_type),
+            ContextRef::Zmq(context) => Ok(OwnedSocket::new(context.socket(socket_type)?, None)),
+        }
+    }
+}
+
+/// A socket which is closed when dropped.
+///
+/// Sockets of a [`Context`] keep it alive and are removed from its open sockets when closed.
+pub struct OwnedSocket {
+    socket: ManuallyDrop<zmq::Socket>,
+    // The libzmq socket of `socket`, which `zmq::Socket` only hands out through `&mut self`.
+    raw: *mut c_void,
+    context: Option<Arc<RawContext>>,
+}
+
+// SAFETY: `raw` is the pointer owned by `socket`, which may be sent to other threads.
+unsafe impl Send for OwnedSocket {}
+
+impl OwnedSocket {
+    fn new(mut socket: zmq::Socket, context: Option<Arc<RawContext>>) -> Self {
+        Self {
+            raw: socket.as_mut_ptr(),
+            socket: ManuallyDrop::new(socket),
+            context,
+        }
+    }
+
+    /// Returns the libzmq socket.
+    pub(crate) fn as_raw(&self) -> *mut c_void {
+        self.raw
+    }
+
+    /// Records an endpoint which the socket has been bound or connected to.
+    pub(crate) fn add_endpoint(&self, endpoint: &str) {
+        if let Some(ref context) = self.context {
+            if let Some(info) = lock(&context.sockets).get_mut(&(self.as_raw() as usize)) {
+                info.endpoints.push(endpoint.to_owned());
+            }
+        }
+    }
+}
+
+impl Deref for OwnedSocket {
+    type Target = zmq::Socket;
+
+    fn deref(&self) -> &zmq::Socket {
+        &self.socket
+    }
+}
+
+impl DerefMut for OwnedSocket {
+    fn deref_mut(&mut self) -> &mut zmq::Socket {
+        &mut self.socket
+    }
+}
+
+impl Drop for OwnedSocket {
+    fn drop(&mut self) {
+        match self.context {
+            Some(ref context) => {
+                // The lock is held while closing, so that a new socket can't reuse the address
+                // before this one has been removed.
+                let mut sockets = lock(&context.sockets);
+                // SAFETY: the socket isn't used after this.
+                unsafe { ManuallyDrop::drop(&mut self.socket) };
+                sockets.remove(&(self.raw as usize));
+            }
+            // SAFETY: the socket isn't used after this.
+            None => unsafe { ManuallyDrop::drop(&mut self.socket) },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RawPtr(*mut c_void);
+
+// The pointer is only used through the thread safe libzmq context API.
+unsafe impl Send for RawPtr {}
+
+struct RawContext {
+    // `None` once the context is being terminated.
+    ctx: Mutex<Option<RawPtr>>,
+    sockets: Mutex<BTreeMap<usize, SocketInfo>>,
+}
+
+impl RawContext {
+    fn new() -> Result<Self> {
+        let ctx = unsafe { zmq_sys::zmq_ctx_new() };
+        if ctx.is_null() {
+            return Err(last_error().into());
+        }
+        Ok(Self {
+            ctx: Mutex::new(Some(RawPtr(ctx))),
+            sockets: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Terminates the context, blocking until all of its sockets have been closed.
+    fn term(&self) -> Result<()> {
+        let ctx = lock(&self.ctx).take();
+        match ctx {
+            Some(ctx) => term(ctx),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RawContext {
+    fn drop(&mut self) {
+        let ctx = lock(&self.ctx).take();
+        if let Some(ctx) = ctx {
+            let term = move || {
+                if let Err(e) = term(ctx) {
+                    log::error!("Failed to terminate context: {}", e);
+                }
+            };
+            // The last socket may be dropped on a runtime worker, which mustn't be blocked for
+            // the linger periods of the sockets.
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => drop(runtime.spawn_blocking(term)),
+                Err(_) => term(),
+            }
+        }
+    }
+}
+
+fn term(RawPtr(ctx): RawPtr) -> Result<()> {
+    while unsafe { zmq_sys::zmq_ctx_term(ctx) } == -1 {
+        let error = last_error();
+        if error != zmq::Error::EINTR {
+            return Err(error.into());
+        }
+    }
+    Ok(())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn last_error() -> zmq::Error {
+    zmq::Error::from_raw(unsafe { zmq_sys::zmq_errno() })
+}
+
+fn raw_socket_type(socket_type: SocketType) -> c_int {
+    let raw = match socket_type {
+        SocketType::PAIR => zmq_sys::ZMQ_PAIR,
+        SocketType::PUB => zmq_sys::ZMQ_PUB,
+        SocketType::SUB => zmq_sys::ZMQ_SUB,
+        SocketType::REQ => zmq_sys::ZMQ_REQ,
+        SocketType::REP => zmq_sys::ZMQ_REP,
+        SocketType::DEALER => zmq_sys::ZMQ_DEALER,
+        SocketType::ROUTER => zmq_sys::ZMQ_ROUTER,
+        SocketType::PULL => zmq_sys::ZMQ_PULL,
+        SocketType::PUSH => zmq_sys::ZMQ_PUSH,
+        SocketType::XPUB => zmq_sys::ZMQ_XPUB,
+        SocketType::XSUB => zmq_sys::ZMQ_XSUB,
+        SocketType::STREAM => zmq_sys::ZMQ_STREAM,
+    };
+    raw as c_int
+}
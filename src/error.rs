@@ -1,14 +1,33 @@
 use thiserror::Error;
 
 /// Error that can occur during an async ZMQ operation.
+///
+/// New variants may be added in minor releases, so matches need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum TmqError {
     /// Inner ZMQ error.
     #[error("Zmq error: {0}")]
     Zmq(#[from] zmq::Error),
-    /// A send operation of a multipart message was successfully started, but it could not be finished.
-    #[error("Interrupted Zmq send. Please report this at https://github.com/cetra3/tmq/issues")]
-    InterruptedSend,
+    /// An operation did not complete before its deadline.
+    #[error("Zmq operation timed out")]
+    Timeout,
+    /// The task driving the socket has stopped.
+    #[error("Socket driver task has stopped")]
+    Closed,
+    /// A message could not be encoded or decoded by a [`Codec`](codec/trait.Codec.html).
+    #[error("Codec error: {0}")]
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+    /// A ROUTER socket with `ZMQ_ROUTER_MANDATORY` could not route a message to a peer.
+    #[error("Host unreachable: {0:?}")]
+    HostUnreachable(crate::router::PeerId),
+    /// A peer sent a message which doesn't follow the protocol of a
+    /// [messaging pattern](patterns/index.html).
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    /// A request got no reply after the given number of attempts.
+    #[error("No reply after {0} attempts")]
+    RetriesExhausted(usize),
     /// General IO error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
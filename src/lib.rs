@@ -10,6 +10,8 @@
 //! * Publish/Subscribe
 //! * Dealer/Router
 //! * Push/Pull
+//! * XPublish/XSubscribe
+//! * Stream
 //! ## Usage
 //!
 //! Usage is made to be simple, but opinionated.   See the [`examples/`](https://github.com/cetra3/tmq/tree/master/examples) Directory for some examples.
@@ -49,14 +51,19 @@
 /// Shortcut for [`Result<T, tmq::TmqError>`].
 pub type Result<T> = std::result::Result<T, TmqError>;
 
-pub use zmq::{Context, Message};
+pub use zmq::Message;
 
+pub use comm::{BufferedReceiver, BufferedSender, ReadHalf, SenderHandle, WriteHalf};
 /// Internal re-exports
+pub use context::{AsContext, Context, ContextBuilder, SocketInfo};
 pub use error::TmqError;
-pub use message::Multipart;
+pub use message::{message_from_bytes, BytesMultipart, Multipart, SinkItem};
+pub use monitor::{HandshakeFailure, SocketEvent, SocketMonitor};
 pub use socket::{AsZmqSocket, SocketExt};
-pub use socket_builder::SocketBuilder;
+pub use socket_builder::{MonitoredSocketBuilder, SocketBuilder};
+pub use socket_io::SocketIo;
 pub use socket_types::*;
+pub use timeout::{Deadline, RecvTimeout, SendTimeout, SocketTimeoutExt};
 
 /// Crate re-exports
 pub(crate) use comm::*;
@@ -64,10 +71,17 @@ pub(crate) use comm::*;
 #[macro_use]
 mod macros;
 
+pub mod codec;
 mod comm;
+mod context;
 mod error;
 mod message;
+mod monitor;
+pub mod patterns;
 mod poll;
+pub mod proxy;
 mod socket;
 mod socket_builder;
+mod socket_io;
 mod socket_types;
+mod timeout;
@@ -1,4 +1,4 @@
-/// Utility implementations
+// Utility implementations
 
 /// Implements common functions for a Socket wrapper.
 macro_rules! impl_wrapper {
@@ -19,10 +19,10 @@ macro_rules! impl_wrapper {
     };
 }
 
-/// Implements `Sink<Multipart, Error=TmqError>` for a Socket wrapping an inner `Sender`.
+/// Implements `Sink<T: SinkItem, Error=TmqError>` for a Socket wrapping an inner `Sender`.
 macro_rules! impl_wrapper_sink {
     ($type: ty, $field: ident) => {
-        impl<T: ::std::convert::Into<$crate::Multipart>> ::futures::Sink<T> for $type {
+        impl<T: $crate::SinkItem> ::futures::Sink<T> for $type {
             type Error = $crate::TmqError;
 
             #[inline]
@@ -87,11 +87,102 @@ macro_rules! impl_as_socket {
     };
 }
 
+/// Implements `into_split` for a Socket wrapping an inner `SenderReceiver`.
+macro_rules! impl_split {
+    ($type: ty, $field: ident) => {
+        impl $type {
+            /// Splits the socket into a [`WriteHalf`](../struct.WriteHalf.html) and a
+            /// [`ReadHalf`](../struct.ReadHalf.html) which can be moved to different tasks.
+            ///
+            /// Unlike `StreamExt::split`, the halves don't need to lock each other for the whole
+            /// duration of a poll, and a pending send never takes over the wakeup of a pending
+            /// receive.
+            pub fn into_split(self) -> ($crate::WriteHalf, $crate::ReadHalf) {
+                self.$field.into_split()
+            }
+        }
+    };
+}
+
+/// Implements `into_io` for a Socket, see [`SocketIo`](../struct.SocketIo.html).
+macro_rules! impl_into_io {
+    ($type: ty) => {
+        impl $type {
+            /// Turns the socket into a byte stream, which implements `AsyncRead` and/or
+            /// `AsyncWrite`. See [`SocketIo`](../struct.SocketIo.html).
+            pub fn into_io(self) -> $crate::SocketIo<Self> {
+                $crate::SocketIo::new(self)
+            }
+        }
+    };
+}
+
 macro_rules! impl_buffered {
     ($type: ty, $field: ident) => {
         impl $type {
-            /// Implements a `buffered` method for the Socket wrapping an inner `Receiver`.
-            pub fn buffered(self, capacity: usize) -> $crate::BufferedReceiver {
+            /// Receives multiparts in batches of up to `capacity` multiparts.
+            ///
+            /// Once all received multiparts have been handed out, every multipart which is
+            /// already waiting (up to `capacity`) is read from the socket at once, which lowers
+            /// the per-message overhead at high message rates. The socket keeps all of its other
+            /// methods.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `capacity` is 0.
+            pub fn buffered(mut self, capacity: usize) -> Self {
+                self.$field
+                    .set_read_buffer($crate::poll::ReceiverBuffer::new(capacity));
+                self
+            }
+
+            /// Like [`buffered`](#method.buffered), but the batch size adapts to the observed
+            /// burst length, between `min` and `max` multiparts.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `min` is 0 or larger than `max`.
+            pub fn buffered_adaptive(mut self, min: usize, max: usize) -> Self {
+                self.$field
+                    .set_read_buffer($crate::poll::ReceiverBuffer::adaptive(min, max));
+                self
+            }
+
+            /// Returns the maximum number of multiparts which are currently read in one batch,
+            /// which is 1 if the socket isn't buffered.
+            pub fn batch_size(&self) -> usize {
+                self.$field.batch_size()
+            }
+        }
+    };
+    // Sockets which hand out a standalone `BufferedReceiver` instead of buffering in place.
+    ($type: ty, $field: ident, $buffered: ty) => {
+        impl $type {
+            /// Turns the socket into a stream which receives multiparts in batches of up to
+            /// `capacity` multiparts.
+            ///
+            /// Once all received multiparts have been handed out, every multipart which is
+            /// already waiting (up to `capacity`) is read from the socket at once, which lowers
+            /// the per-message overhead at high message rates.
+            pub fn buffered(self, capacity: usize) -> $buffered {
+                self.$field.buffered(capacity)
+            }
+
+            /// Like [`buffered`](#method.buffered), but the batch size adapts to the observed
+            /// burst length, between `min` and `max` multiparts.
+            pub fn buffered_adaptive(self, min: usize, max: usize) -> $buffered {
+                self.$field.buffered_adaptive(min, max)
+            }
+        }
+    };
+}
+
+macro_rules! impl_buffered_sender {
+    ($type: ty, $field: ident) => {
+        impl $type {
+            /// Turns the socket into a sink which queues up to `capacity` multiparts, see
+            /// [`BufferedSender`]($crate::BufferedSender).
+            pub fn buffered(self, capacity: usize) -> $crate::BufferedSender {
                 self.$field.buffered(capacity)
             }
         }
@@ -99,12 +190,12 @@ macro_rules! impl_buffered {
 }
 
 /// Async read/write implementations
-/// Implements Sink<T: Into<Multipart>> for the given type.
-/// $buffer: identifier of a field containing a `Multipart`
+/// Implements Sink<T: SinkItem> for the given type.
+/// $buffer: identifier of a field containing `Frames`
 /// $socket: identifier of a field containing a `ZmqPoller`
 macro_rules! impl_sink {
     ($type: ty, $buffer: ident, $socket: ident) => {
-        impl<T: ::std::convert::Into<$crate::Multipart>> ::futures::Sink<T> for $type {
+        impl<T: $crate::SinkItem> ::futures::Sink<T> for $type {
             type Error = $crate::TmqError;
 
             fn poll_ready(
@@ -114,6 +205,7 @@ macro_rules! impl_sink {
                 let Self {
                     ref $socket,
                     ref mut $buffer,
+                    ..
                 } = self.get_mut();
                 ::futures::ready!($socket.multipart_flush(cx, $buffer))?;
                 ::std::task::Poll::Ready($crate::Result::Ok(()))
@@ -121,7 +213,7 @@ macro_rules! impl_sink {
 
             fn start_send(mut self: ::std::pin::Pin<&mut Self>, item: T) -> $crate::Result<()> {
                 assert!(self.$buffer.is_empty());
-                self.$buffer = item.into();
+                self.$buffer = $crate::message::into_frames(item);
                 $crate::Result::Ok(())
             }
 
@@ -132,6 +224,7 @@ macro_rules! impl_sink {
                 let Self {
                     ref $socket,
                     ref mut $buffer,
+                    ..
                 } = self.get_mut();
                 $socket.multipart_flush(cx, $buffer)
             }
@@ -147,9 +240,10 @@ macro_rules! impl_sink {
 }
 
 /// Implements `Stream<Item=Result<Multipart>>` for the given type.
+/// $read_buffer: identifier of a field containing an `Option<ReceiverBuffer>`
 /// $socket: identifier of a field containing a `ZmqPoller`
 macro_rules! impl_stream {
-    ($type: ty, $socket: ident) => {
+    ($type: ty, $read_buffer: ident, $socket: ident) => {
         impl ::futures::Stream for $type {
             type Item = $crate::Result<$crate::Multipart>;
 
@@ -157,7 +251,12 @@ macro_rules! impl_stream {
                 self: ::std::pin::Pin<&mut Self>,
                 cx: &mut ::std::task::Context,
             ) -> ::std::task::Poll<::std::option::Option<Self::Item>> {
-                match self.$socket.multipart_recv(cx) {
+                let Self {
+                    ref $socket,
+                    ref mut $read_buffer,
+                    ..
+                } = self.get_mut();
+                match $socket.multipart_recv_with(cx, $read_buffer) {
                     ::std::task::Poll::Ready(value) => {
                         ::std::task::Poll::Ready(::std::option::Option::Some(value))
                     }
@@ -182,6 +281,7 @@ macro_rules! impl_buffered_stream {
                 let Self {
                     ref $socket,
                     ref mut $buffer,
+                    ..
                 } = self.get_mut();
                 match $socket.multipart_recv_buffered(cx, $buffer) {
                     ::std::task::Poll::Ready(value) => {
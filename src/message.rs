@@ -5,6 +5,8 @@ use std::{
 };
 use zmq::Message;
 
+use bytes::Bytes;
+
 /// ZMQ multipart which holds individual messages.
 ///
 /// It is implemented with a VecDeque to allow efficient popping from the beginning.
@@ -60,6 +62,227 @@ impl Multipart {
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Message> {
         self.0.iter_mut()
     }
+
+    /// Converts the messages of this multipart into [`Bytes`].
+    ///
+    /// Frames larger than a few dozen bytes are not copied, the resulting `Bytes` keep the
+    /// received message alive instead. Smaller frames are copied, which is cheaper.
+    pub fn into_bytes_frames(self) -> Vec<Bytes> {
+        self.0
+            .into_iter()
+            .map(|message| {
+                if message.len() <= MAX_COPIED_FRAME_SIZE {
+                    Bytes::copy_from_slice(&message)
+                } else {
+                    Bytes::from_owner(MessageOwner(message))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Removes the routing envelope from a message received by a ROUTER socket.
+///
+/// The envelope is the identity frame, followed by the empty delimiter of REQ peers if one comes
+/// directly after it. With `delimited`, the envelope extends to the first empty frame instead, like
+/// in REP sockets, which also covers requests routed through proxies.
+pub(crate) fn split_envelope(multipart: &mut Multipart, delimited: bool) -> Multipart {
+    let len = if delimited {
+        match multipart.iter().position(|frame| frame.is_empty()) {
+            Some(delimiter) => delimiter + 1,
+            None => 1,
+        }
+    } else if multipart.len() > 1 && multipart[1].is_empty() {
+        2
+    } else {
+        1
+    };
+    multipart.0.drain(..len.min(multipart.len())).collect()
+}
+
+/// Frames up to this size are stored inline in the `zmq_msg_t` by libzmq and are cheaper to copy
+/// than to share.
+const MAX_COPIED_FRAME_SIZE: usize = 33;
+
+struct MessageOwner(Message);
+
+impl AsRef<[u8]> for MessageOwner {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Creates a message from `bytes`, without copying them if possible.
+///
+/// The memory is only handed over to the message if `bytes` is its unique owner, e.g. a `Bytes`
+/// created from a `Vec<u8>` which hasn't been cloned. Otherwise the bytes are copied, as messages
+/// can be modified through `DerefMut`, which must not change memory shared with other handles.
+/// Use [`BytesMultipart`] to send `Bytes` without ever copying them.
+///
+/// Multiparts of several `Bytes` can be built with
+/// `frames.into_iter().map(message_from_bytes).collect::<Multipart>()`.
+pub fn message_from_bytes(bytes: Bytes) -> Message {
+    match bytes.try_into_mut() {
+        Ok(unique) => Message::from(Vec::from(unique)),
+        Err(shared) => Message::from(&shared[..]),
+    }
+}
+
+impl From<Bytes> for Multipart {
+    fn from(bytes: Bytes) -> Self {
+        message_from_bytes(bytes).into()
+    }
+}
+
+/// Multipart of [`Bytes`] frames, which are sent without being copied.
+///
+/// Each frame is handed to libzmq with `zmq_msg_init_data`, which keeps a reference to the
+/// `Bytes` until the frame has been sent. As the frames can't be modified, they may share their
+/// memory with other `Bytes` handles. Every socket `Sink` accepts a `BytesMultipart`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BytesMultipart(pub VecDeque<Bytes>);
+
+impl BytesMultipart {
+    /// Returns `true` if the multipart contains no frames.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of frames in the multipart.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Adds a frame to the front of the multipart.
+    #[inline]
+    pub fn push_front(&mut self, frame: Bytes) {
+        self.0.push_front(frame)
+    }
+
+    /// Adds a frame to the back of the multipart.
+    #[inline]
+    pub fn push_back(&mut self, frame: Bytes) {
+        self.0.push_back(frame)
+    }
+
+    /// Creates an iterator which iterates through the frames of this multipart.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Bytes> {
+        self.0.iter()
+    }
+}
+
+impl From<Bytes> for BytesMultipart {
+    fn from(frame: Bytes) -> Self {
+        Self(VecDeque::from([frame]))
+    }
+}
+
+impl From<Vec<Bytes>> for BytesMultipart {
+    fn from(frames: Vec<Bytes>) -> Self {
+        Self(frames.into())
+    }
+}
+
+impl FromIterator<Bytes> for BytesMultipart {
+    fn from_iter<T: IntoIterator<Item = Bytes>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for BytesMultipart {
+    type Item = Bytes;
+    type IntoIter = std::collections::vec_deque::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Items which the `Sink` implementations of the sockets accept: everything which converts into
+/// a [`Multipart`], and [`BytesMultipart`].
+pub trait SinkItem: private::Sealed {}
+
+impl<T: private::Sealed> SinkItem for T {}
+
+mod private {
+    use std::collections::VecDeque;
+
+    use zmq::Message;
+
+    use bytes::Bytes;
+
+    /// A frame which is waiting to be sent.
+    pub enum Frame {
+        Message(Message),
+        Bytes(Bytes),
+    }
+
+    impl Frame {
+        pub fn is_empty(&self) -> bool {
+            match self {
+                Frame::Message(message) => message.is_empty(),
+                Frame::Bytes(bytes) => bytes.is_empty(),
+            }
+        }
+    }
+
+    /// The frames of a multipart which is being sent.
+    #[derive(Default)]
+    pub struct Frames(pub VecDeque<Frame>);
+
+    pub trait Sealed {
+        fn into_frames(self) -> Frames;
+    }
+}
+
+pub(crate) use private::{Frame, Frames};
+
+impl Frames {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn push_front(&mut self, frame: Frame) {
+        self.0.push_front(frame)
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<Frame> {
+        self.0.pop_front()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear()
+    }
+}
+
+impl From<Multipart> for Frames {
+    fn from(multipart: Multipart) -> Self {
+        Self(multipart.into_iter().map(Frame::Message).collect())
+    }
+}
+
+impl<T: Into<Multipart>> private::Sealed for T {
+    fn into_frames(self) -> Frames {
+        self.into().into()
+    }
+}
+
+impl private::Sealed for BytesMultipart {
+    fn into_frames(self) -> Frames {
+        Frames(self.into_iter().map(Frame::Bytes).collect())
+    }
+}
+
+/// Converts a sink item into the frames which are sent.
+pub(crate) fn into_frames<T: SinkItem>(item: T) -> Frames {
+    private::Sealed::into_frames(item)
 }
 
 impl<T: Into<Message>> From<Vec<T>> for Multipart {
@@ -0,0 +1,284 @@
+use std::{
+    os::unix::io::RawFd,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use futures::{ready, Stream};
+
+use crate::{comm::Receiver, context::ContextRef, poll::ZmqPoller, Multipart, Result};
+
+/// Reason of a failed security handshake, reported by [`SocketEvent::HandshakeFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeFailure {
+    /// Unspecified failure, carries the errno value.
+    NoDetail(i32),
+    /// Protocol error, carries one of the `ZMQ_PROTOCOL_ERROR_*` codes.
+    Protocol(u32),
+    /// Authentication failure, carries the ZAP status code.
+    Auth(u32),
+}
+
+/// Event reported by a socket monitor.
+///
+/// See [http://api.zeromq.org/4-3:zmq-socket-monitor](http://api.zeromq.org/4-3:zmq-socket-monitor)
+/// for a description of the individual events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketEvent {
+    /// The socket has successfully connected to a remote peer.
+    Connected {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// File descriptor of the underlying connection.
+        fd: RawFd,
+    },
+    /// A synchronous connect failed and the connection is being polled.
+    ConnectDelayed {
+        /// Endpoint of the connection.
+        endpoint: String,
+    },
+    /// A connection attempt is being retried.
+    ConnectRetried {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// Reconnection interval in milliseconds.
+        interval: u32,
+    },
+    /// The socket was bound to an endpoint and is ready to accept connections.
+    Listening {
+        /// Endpoint the socket is bound to.
+        endpoint: String,
+        /// File descriptor of the listening socket.
+        fd: RawFd,
+    },
+    /// The socket could not bind to an endpoint.
+    BindFailed {
+        /// Endpoint of the failed bind.
+        endpoint: String,
+        /// Errno value of the failure.
+        error: i32,
+    },
+    /// The socket has accepted a connection from a remote peer.
+    Accepted {
+        /// Endpoint the connection was accepted on.
+        endpoint: String,
+        /// File descriptor of the accepted connection.
+        fd: RawFd,
+    },
+    /// The socket has rejected a connection from a remote peer.
+    AcceptFailed {
+        /// Endpoint of the rejected connection.
+        endpoint: String,
+        /// Errno value of the failure.
+        error: i32,
+    },
+    /// A connection's underlying descriptor has been closed.
+    Closed {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// File descriptor of the closed connection.
+        fd: RawFd,
+    },
+    /// A descriptor could not be released back to the OS.
+    CloseFailed {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// Errno value of the failure.
+        error: i32,
+    },
+    /// The session was broken unexpectedly.
+    Disconnected {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// File descriptor of the disconnected connection.
+        fd: RawFd,
+    },
+    /// Monitoring of the socket has stopped, no more events will follow.
+    MonitorStopped {
+        /// Endpoint of the event, usually empty.
+        endpoint: String,
+    },
+    /// The security handshake with a peer succeeded.
+    HandshakeSucceeded {
+        /// Endpoint of the connection.
+        endpoint: String,
+    },
+    /// The security handshake with a peer failed.
+    HandshakeFailed {
+        /// Endpoint of the connection.
+        endpoint: String,
+        /// Reason of the failure.
+        reason: HandshakeFailure,
+    },
+    /// An event unknown to this crate.
+    Unknown {
+        /// Endpoint of the event.
+        endpoint: String,
+        /// Raw event number.
+        event: u16,
+        /// Raw event value.
+        value: u32,
+    },
+}
+
+impl SocketEvent {
+    /// Returns the endpoint this event relates to.
+    pub fn endpoint(&self) -> &str {
+        match self {
+            SocketEvent::Connected { endpoint, .. }
+            | SocketEvent::ConnectDelayed { endpoint }
+            | SocketEvent::ConnectRetried { endpoint, .. }
+            | SocketEvent::Listening { endpoint, .. }
+            | SocketEvent::BindFailed { endpoint, .. }
+            | SocketEvent::Accepted { endpoint, .. }
+            | SocketEvent::AcceptFailed { endpoint, .. }
+            | SocketEvent::Closed { endpoint, .. }
+            | SocketEvent::CloseFailed { endpoint, .. }
+            | SocketEvent::Disconnected { endpoint, .. }
+            | SocketEvent::MonitorStopped { endpoint }
+            | SocketEvent::HandshakeSucceeded { endpoint }
+            | SocketEvent::HandshakeFailed { endpoint, .. }
+            | SocketEvent::Unknown { endpoint, .. } => endpoint,
+        }
+    }
+
+    /// Decodes a monitor message, which consists of a 6-byte frame with the event number and value
+    /// followed by a frame with the endpoint.
+    pub fn from_multipart(multipart: &Multipart) -> Option<Self> {
+        if multipart.len() != 2 || multipart[0].len() != 6 {
+            return None;
+        }
+        let event = u16::from_ne_bytes([multipart[0][0], multipart[0][1]]);
+        let value = u32::from_ne_bytes([
+            multipart[0][2],
+            multipart[0][3],
+            multipart[0][4],
+            multipart[0][5],
+        ]);
+        let endpoint = String::from_utf8_lossy(&multipart[1]).into_owned();
+
+        let event = match u32::from(event) {
+            zmq_sys::ZMQ_EVENT_CONNECTED => SocketEvent::Connected {
+                endpoint,
+                fd: value as RawFd,
+            },
+            zmq_sys::ZMQ_EVENT_CONNECT_DELAYED => SocketEvent::ConnectDelayed { endpoint },
+            zmq_sys::ZMQ_EVENT_CONNECT_RETRIED => SocketEvent::ConnectRetried {
+                endpoint,
+                interval: value,
+            },
+            zmq_sys::ZMQ_EVENT_LISTENING => SocketEvent::Listening {
+                endpoint,
+                fd: value as RawFd,
+            },
+            zmq_sys::ZMQ_EVENT_BIND_FAILED => SocketEvent::BindFailed {
+                endpoint,
+                error: value as i32,
+            },
+            zmq_sys::ZMQ_EVENT_ACCEPTED => SocketEvent::Accepted {
+                endpoint,
+                fd: value as RawFd,
+            },
+            zmq_sys::ZMQ_EVENT_ACCEPT_FAILED => SocketEvent::AcceptFailed {
+                endpoint,
+                error: value as i32,
+            },
+            zmq_sys::ZMQ_EVENT_CLOSED => SocketEvent::Closed {
+                endpoint,
+                fd: value as RawFd,
+            },
+            zmq_sys::ZMQ_EVENT_CLOSE_FAILED => SocketEvent::CloseFailed {
+                endpoint,
+                error: value as i32,
+            },
+            zmq_sys::ZMQ_EVENT_DISCONNECTED => SocketEvent::Disconnected {
+                endpoint,
+                fd: value as RawFd,
+            },
+            zmq_sys::ZMQ_EVENT_MONITOR_STOPPED => SocketEvent::MonitorStopped { endpoint },
+            zmq_sys::ZMQ_EVENT_HANDSHAKE_SUCCEEDED => SocketEvent::HandshakeSucceeded { endpoint },
+            zmq_sys::ZMQ_EVENT_HANDSHAKE_FAILED_NO_DETAIL => SocketEvent::HandshakeFailed {
+                endpoint,
+                reason: HandshakeFailure::NoDetail(value as i32),
+            },
+            zmq_sys::ZMQ_EVENT_HANDSHAKE_FAILED_PROTOCOL => SocketEvent::HandshakeFailed {
+                endpoint,
+                reason: HandshakeFailure::Protocol(value),
+            },
+            zmq_sys::ZMQ_EVENT_HANDSHAKE_FAILED_AUTH => SocketEvent::HandshakeFailed {
+                endpoint,
+                reason: HandshakeFailure::Auth(value),
+            },
+            _ => SocketEvent::Unknown {
+                endpoint,
+                event,
+                value,
+            },
+        };
+        Some(event)
+    }
+}
+
+/// Stream of [`SocketEvent`]s of a monitored socket.
+///
+/// Returned together with the socket by [`SocketBuilder::monitored`](struct.SocketBuilder.html#method.monitored).
+///
+/// The stream ends after [`SocketEvent::MonitorStopped`], which is sent when the monitored socket
+/// is closed, regardless of the requested events.
+pub struct SocketMonitor {
+    inner: Receiver,
+    stopped: bool,
+}
+
+impl SocketMonitor {
+    /// Starts monitoring `socket` on a fresh inproc endpoint and connects a PAIR socket to it.
+    pub(crate) fn new(context: &ContextRef, socket: &zmq::Socket, events: i32) -> Result<Self> {
+        static MONITOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let endpoint = format!(
+            "inproc://tmq-monitor-{}",
+            MONITOR_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        // MONITOR_STOPPED is always requested so that the stream can end.
+        socket.monitor(
+            &endpoint,
+            events | zmq_sys::ZMQ_EVENT_MONITOR_STOPPED as i32,
+        )?;
+
+        let pair = context.socket(zmq::SocketType::PAIR)?;
+        pair.connect(&endpoint)?;
+        Ok(Self {
+            inner: Receiver::new(ZmqPoller::from_zmq_socket(pair)?),
+            stopped: false,
+        })
+    }
+}
+
+impl_as_socket!(SocketMonitor, inner);
+
+impl Stream for SocketMonitor {
+    type Item = Result<SocketEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.stopped {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            let multipart = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            match SocketEvent::from_multipart(&multipart) {
+                Some(event) => {
+                    self.stopped = matches!(event, SocketEvent::MonitorStopped { .. });
+                    return Poll::Ready(Some(Ok(event)));
+                }
+                None => log::warn!("Ignoring malformed socket monitor message"),
+            }
+        }
+    }
+}
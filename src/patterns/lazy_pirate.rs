@@ -0,0 +1,160 @@
+//! Lazy Pirate: a REQ client which recovers from lost replies by retrying.
+//!
+//! A REQ socket waits for the reply of a request forever, so a server which dies while handling
+//! it stalls the client. The [`LazyPirateClient`] waits for a reply for a limited time only. When
+//! the time is up, it closes the socket, opens a new one and sends the request again, possibly to
+//! the next of several servers.
+//!
+//! Requests may be handled more than once, so they should be idempotent.
+//!
+//! ## Usage Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tmq::{patterns::lazy_pirate::LazyPirateClient, Context, Result};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let mut client = LazyPirateClient::new(
+//!         &Context::new(),
+//!         vec!["tcp://127.0.0.1:5555", "tcp://127.0.0.1:5556"],
+//!     )
+//!     .with_timeout(Duration::from_secs(1))
+//!     .with_retries(5);
+//!
+//!     let reply = client.request(vec!["hello"]).await?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::time::Duration;
+
+use super::copy;
+use crate::{
+    context::ContextRef, request, request_reply::RequestSender, AsContext, Multipart, Result,
+    SocketBuilder, TmqError,
+};
+
+/// Default time to wait for a reply before retrying.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_millis(2500);
+/// Default number of retries after the first attempt of a request.
+pub const REQUEST_RETRIES: usize = 2;
+
+type Configure = Box<dyn Fn(SocketBuilder<RequestSender>) -> SocketBuilder<RequestSender> + Send>;
+
+/// Reliable REQ client, which retries requests on new sockets and rotates through its servers.
+///
+/// The socket is created lazily by the first request. After a request has timed out, the socket
+/// is closed without waiting for pending messages (`ZMQ_LINGER` is 0) and the next attempt uses a
+/// new socket, connected to the next endpoint. The client stays connected to an endpoint for as
+/// long as it replies in time.
+pub struct LazyPirateClient {
+    context: ContextRef,
+    endpoints: Vec<String>,
+    current: usize,
+    timeout: Duration,
+    retries: usize,
+    configure: Configure,
+    socket: Option<RequestSender>,
+}
+
+impl LazyPirateClient {
+    /// Creates a client for the servers at `endpoints`, the first one is tried first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `endpoints` is empty.
+    pub fn new<I, E>(context: &impl AsContext, endpoints: I) -> Self
+    where
+        I: IntoIterator<Item = E>,
+        E: Into<String>,
+    {
+        let endpoints: Vec<String> = endpoints.into_iter().map(Into::into).collect();
+        assert!(!endpoints.is_empty(), "no endpoints given");
+        Self {
+            context: ContextRef::from_context(context),
+            endpoints,
+            current: 0,
+            timeout: REQUEST_TIMEOUT,
+            retries: REQUEST_RETRIES,
+            configure: Box::new(|builder| builder),
+            socket: None,
+        }
+    }
+
+    /// Sets the time to wait for a reply, which includes sending the request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of retries after the first attempt of a request.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets a function which sets options of every socket before it is connected.
+    ///
+    /// ```rust,no_run
+    /// # use tmq::{patterns::lazy_pirate::LazyPirateClient, Context};
+    /// let client = LazyPirateClient::new(&Context::new(), vec!["tcp://127.0.0.1:5555"])
+    ///     .with_socket_options(|builder| builder.set_ipv6(true));
+    /// ```
+    pub fn with_socket_options<F>(mut self, configure: F) -> Self
+    where
+        F: Fn(SocketBuilder<RequestSender>) -> SocketBuilder<RequestSender> + Send + 'static,
+    {
+        self.configure = Box::new(configure);
+        self
+    }
+
+    /// Returns the endpoint which the next attempt is sent to.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoints[self.current]
+    }
+
+    /// Sends a request and waits for its reply, retrying on timeouts.
+    ///
+    /// Returns [`TmqError::RetriesExhausted`] if no attempt got a reply in time. Other errors are
+    /// returned right away, the next request uses a new socket then.
+    pub async fn request<M: Into<Multipart>>(&mut self, request: M) -> Result<Multipart> {
+        let request = request.into();
+        let attempts = self.retries + 1;
+
+        for attempt in 1..=attempts {
+            let socket = match self.socket.take() {
+                Some(socket) => socket,
+                None => self.connect()?,
+            };
+
+            let exchange = async {
+                let receiver = socket.send(copy(&request)).await?;
+                receiver.recv().await
+            };
+            match tokio::time::timeout(self.timeout, exchange).await {
+                Ok(Ok((reply, socket))) => {
+                    self.socket = Some(socket);
+                    return Ok(reply);
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    log::warn!(
+                        "No reply from {} (attempt {} of {})",
+                        self.endpoint(),
+                        attempt,
+                        attempts
+                    );
+                    self.current = (self.current + 1) % self.endpoints.len();
+                }
+            }
+        }
+
+        Err(TmqError::RetriesExhausted(attempts))
+    }
+
+    fn connect(&self) -> Result<RequestSender> {
+        let builder = request(&self.context).set_linger(0);
+        (self.configure)(builder).connect(self.endpoint())
+    }
+}
@@ -0,0 +1,698 @@
+//! Majordomo Protocol ([MDP/0.2](https://rfc.zeromq.org/spec/18/)) broker, client and worker.
+//!
+//! Clients send requests for a named service to the [`MdpBroker`], which queues them per service
+//! and hands them to the workers which have registered for the service. Workers and the broker
+//! exchange heartbeats, so that either side notices when the other one has gone away: the broker
+//! forgets workers which have stopped sending heartbeats and hands their requests to other
+//! workers, and an [`MdpWorker`] reconnects when the broker stops sending them.
+//!
+//! The messages follow the MDP/0.2 wire format, where clients and workers use DEALER sockets, so
+//! the broker, client and worker can be used together with MDP/0.2 peers written in other
+//! languages. The broker also answers `mmi.service` requests of the
+//! [MMI](https://rfc.zeromq.org/spec/8/) extension.
+//!
+//! ## Usage Example
+//!
+//! ```rust,no_run
+//! use futures::StreamExt;
+//! use tmq::{
+//!     patterns::majordomo::{MdpBroker, MdpClient, MdpWorker},
+//!     Context, Result,
+//! };
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let ctx = Context::new();
+//!     let broker = MdpBroker::bind(&ctx, "tcp://127.0.0.1:5555")?;
+//!     tokio::spawn(broker.run());
+//!
+//!     let mut worker = MdpWorker::connect(&ctx, "tcp://127.0.0.1:5555", "echo")?;
+//!     tokio::spawn(async move {
+//!         while let Some(request) = worker.next().await {
+//!             let mut request = request?;
+//!             let body = std::mem::take(request.body_mut());
+//!             request.reply(body)?;
+//!         }
+//!         Ok::<_, tmq::TmqError>(())
+//!     });
+//!
+//!     let mut client = MdpClient::connect(&ctx, "tcp://127.0.0.1:5555")?;
+//!     let reply = client.request("echo", vec!["hello"]).await?;
+//!     assert_eq!(&reply[0][..], b"hello");
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, future::poll_fn, ready, SinkExt, Stream, StreamExt};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use super::{copy, WorkerSocket};
+use crate::{
+    context::ContextRef,
+    dealer,
+    dealer::Dealer,
+    router,
+    router::{PeerId, Router},
+    socket::AsZmqSocket,
+    AsContext, Message, Multipart, Result, TmqError,
+};
+
+/// Protocol header of the messages between clients and the broker.
+pub const CLIENT_HEADER: &[u8] = b"MDPC02";
+/// Protocol header of the messages between workers and the broker.
+pub const WORKER_HEADER: &[u8] = b"MDPW02";
+
+// Client commands.
+const REQUEST: u8 = 0x01;
+const PARTIAL: u8 = 0x02;
+const FINAL: u8 = 0x03;
+
+// Worker commands.
+const WORKER_READY: u8 = 0x01;
+const WORKER_REQUEST: u8 = 0x02;
+const WORKER_PARTIAL: u8 = 0x03;
+const WORKER_FINAL: u8 = 0x04;
+const WORKER_HEARTBEAT: u8 = 0x05;
+const WORKER_DISCONNECT: u8 = 0x06;
+
+/// Default interval of the heartbeats between the broker and workers.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(2500);
+/// Default number of heartbeats which can be missed before the other side is considered dead.
+pub const HEARTBEAT_LIVENESS: u32 = 3;
+
+fn command(header: &[u8], command: u8) -> Multipart {
+    vec![Message::from(header), Message::from(&[command][..])].into()
+}
+
+fn prepend(mut body: Multipart, frames: Vec<Message>) -> Multipart {
+    for frame in frames.into_iter().rev() {
+        body.push_front(frame);
+    }
+    body
+}
+
+/// Removes the header and the command from a message, returning the command.
+fn pop_command(multipart: &mut Multipart, header: &[u8]) -> Option<u8> {
+    match multipart.pop_front() {
+        Some(frame) if &frame[..] == header => {}
+        _ => return None,
+    }
+    match multipart.pop_front() {
+        Some(frame) if frame.len() == 1 => Some(frame[0]),
+        _ => None,
+    }
+}
+
+/// Majordomo broker, which routes requests of clients to the workers of their service.
+///
+/// Requests are queued per service until a worker of the service is available, the workers of a
+/// service get requests in the order in which they have become ready.
+/// Heartbeats are sent to all workers. A worker which hasn't sent anything for `liveness`
+/// heartbeat intervals is forgotten. If it was handling a request, the request is queued again
+/// in front of the other requests of the service, so it may be handled more than once.
+/// Replies which a forgotten worker sends later are dropped.
+pub struct MdpBroker {
+    socket: Router,
+    services: HashMap<Vec<u8>, Service>,
+    workers: HashMap<PeerId, Worker>,
+    heartbeat_interval: Duration,
+    liveness: u32,
+    // Messages which have to be sent after handling an event.
+    outgoing: VecDeque<Multipart>,
+}
+
+#[derive(Default)]
+struct Service {
+    // Clients and bodies of the queued requests.
+    requests: VecDeque<(Message, Multipart)>,
+    // Workers which wait for a request.
+    waiting: VecDeque<PeerId>,
+}
+
+struct Worker {
+    service: Vec<u8>,
+    expiry: Instant,
+    // Client and body of the request which the worker is handling.
+    request: Option<(Message, Multipart)>,
+}
+
+impl MdpBroker {
+    /// Creates a broker which serves clients and workers on the given ROUTER socket.
+    pub fn new(socket: Router) -> Self {
+        Self {
+            socket,
+            services: HashMap::new(),
+            workers: HashMap::new(),
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+            liveness: HEARTBEAT_LIVENESS,
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    /// Creates a broker on a ROUTER socket which is bound to `endpoint`.
+    pub fn bind(context: &impl AsContext, endpoint: &str) -> Result<Self> {
+        Ok(Self::new(router(context).bind(endpoint)?))
+    }
+
+    /// Sets the heartbeat interval and the number of heartbeats which a worker can miss.
+    ///
+    /// The workers should use the same settings, see [`MdpWorker::with_heartbeat`].
+    pub fn with_heartbeat(mut self, interval: Duration, liveness: u32) -> Self {
+        self.heartbeat_interval = interval;
+        self.liveness = liveness;
+        self
+    }
+
+    /// Runs the broker until its socket fails.
+    pub async fn run(mut self) -> Result<()> {
+        let mut heartbeat = tokio::time::interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let message = poll_fn(|cx| self.poll_event(cx, &mut heartbeat)).await;
+            match message {
+                Some(message) => self.handle_message(message?),
+                None => self.heartbeat(),
+            }
+            while let Some(multipart) = self.outgoing.pop_front() {
+                self.socket.send(multipart).await?;
+            }
+        }
+    }
+
+    /// Returns the next message, or `None` when it's time to send heartbeats.
+    fn poll_event(
+        &mut self,
+        cx: &mut Context<'_>,
+        heartbeat: &mut Interval,
+    ) -> Poll<Option<Result<Multipart>>> {
+        if heartbeat.poll_tick(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        match ready!(self.socket.poll_next_unpin(cx)) {
+            Some(message) => Poll::Ready(Some(message)),
+            None => Poll::Ready(Some(Err(TmqError::Closed))),
+        }
+    }
+
+    fn handle_message(&mut self, mut multipart: Multipart) {
+        let sender = match multipart.pop_front() {
+            Some(sender) => sender,
+            None => return,
+        };
+        match multipart.0.front() {
+            Some(header) if &header[..] == CLIENT_HEADER => self.handle_client(sender, multipart),
+            Some(header) if &header[..] == WORKER_HEADER => {
+                self.handle_worker(PeerId::from(&sender), multipart)
+            }
+            _ => log::warn!(
+                "Ignoring invalid MDP message from {:?}",
+                PeerId::from(&sender)
+            ),
+        }
+    }
+
+    fn handle_client(&mut self, client: Message, mut multipart: Multipart) {
+        if pop_command(&mut multipart, CLIENT_HEADER) != Some(REQUEST) {
+            log::warn!(
+                "Ignoring invalid MDP client message from {:?}",
+                PeerId::from(&client)
+            );
+            return;
+        }
+        let service = match multipart.pop_front() {
+            Some(service) => service.to_vec(),
+            None => return,
+        };
+
+        if service.starts_with(b"mmi.") {
+            let status: &[u8] = if service == b"mmi.service" {
+                let requested = multipart.0.front().map(|name| &name[..]);
+                let registered = |worker: &Worker| Some(&worker.service[..]) == requested;
+                if self.workers.values().any(registered) {
+                    b"200"
+                } else {
+                    b"404"
+                }
+            } else {
+                b"501"
+            };
+            let reply = vec![
+                client,
+                CLIENT_HEADER.into(),
+                (&[FINAL][..]).into(),
+                service.into(),
+                status.into(),
+            ];
+            self.outgoing.push_back(reply.into());
+            return;
+        }
+
+        self.services
+            .entry(service.clone())
+            .or_default()
+            .requests
+            .push_back((client, multipart));
+        self.dispatch(&service);
+    }
+
+    fn handle_worker(&mut self, peer: PeerId, mut multipart: Multipart) {
+        let command = pop_command(&mut multipart, WORKER_HEADER);
+        let expiry = Instant::now() + self.heartbeat_interval * self.liveness;
+        let known = match self.workers.get_mut(&peer) {
+            Some(worker) => {
+                worker.expiry = expiry;
+                true
+            }
+            None => false,
+        };
+
+        match command {
+            Some(WORKER_READY) if !known => {
+                let service = match multipart.pop_front() {
+                    Some(service) => service.to_vec(),
+                    None => return self.disconnect(peer),
+                };
+                log::debug!("Worker {:?} ready for service {:?}", peer, service);
+                self.workers.insert(
+                    peer.clone(),
+                    Worker {
+                        service: service.clone(),
+                        expiry,
+                        request: None,
+                    },
+                );
+                self.services
+                    .entry(service.clone())
+                    .or_default()
+                    .waiting
+                    .push_back(peer);
+                self.dispatch(&service);
+            }
+            Some(command @ (WORKER_PARTIAL | WORKER_FINAL)) if known => {
+                let client = multipart.pop_front();
+                let delimiter = multipart.pop_front();
+                // Replies are only accepted for the request which the worker is handling.
+                let outstanding = match self.workers[&peer].request {
+                    Some((ref expected, _)) => client.as_ref() == Some(expected),
+                    None => false,
+                };
+                let client = match (client, delimiter) {
+                    (Some(client), Some(delimiter)) if delimiter.is_empty() && outstanding => {
+                        client
+                    }
+                    _ => {
+                        log::warn!("Disconnecting worker {:?} after unexpected reply", peer);
+                        return self.disconnect(peer);
+                    }
+                };
+                let service = self.workers[&peer].service.clone();
+                let reply_command = if command == WORKER_FINAL {
+                    FINAL
+                } else {
+                    PARTIAL
+                };
+                let header = vec![
+                    client,
+                    CLIENT_HEADER.into(),
+                    (&[reply_command][..]).into(),
+                    Message::from(&service[..]),
+                ];
+                self.outgoing.push_back(prepend(multipart, header));
+
+                if command == WORKER_FINAL {
+                    self.workers.get_mut(&peer).unwrap().request = None;
+                    self.services
+                        .entry(service.clone())
+                        .or_default()
+                        .waiting
+                        .push_back(peer);
+                    self.dispatch(&service);
+                }
+            }
+            Some(WORKER_HEARTBEAT) if known => {}
+            Some(WORKER_DISCONNECT) => self.remove_worker(&peer),
+            _ => {
+                log::warn!("Disconnecting worker {:?} after invalid message", peer);
+                self.disconnect(peer);
+            }
+        }
+    }
+
+    /// Sends queued requests of the service to its waiting workers.
+    fn dispatch(&mut self, service: &[u8]) {
+        let service = match self.services.get_mut(service) {
+            Some(service) => service,
+            None => return,
+        };
+        while !service.requests.is_empty() {
+            let worker = match service.waiting.pop_front() {
+                Some(worker) => worker,
+                None => break,
+            };
+            let (client, body) = service.requests.pop_front().unwrap();
+            let request = (Message::from(&client[..]), copy(&body));
+            if let Some(worker) = self.workers.get_mut(&worker) {
+                worker.request = Some(request);
+            }
+            let header = vec![
+                Message::from(worker),
+                WORKER_HEADER.into(),
+                (&[WORKER_REQUEST][..]).into(),
+                client,
+                Message::new(),
+            ];
+            self.outgoing.push_back(prepend(body, header));
+        }
+    }
+
+    /// Forgets expired workers and sends heartbeats to the others.
+    fn heartbeat(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<PeerId> = self
+            .workers
+            .iter()
+            .filter(|(_, worker)| worker.expiry <= now)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+        for peer in expired {
+            log::debug!("Worker {:?} expired", peer);
+            self.remove_worker(&peer);
+        }
+        for peer in self.workers.keys() {
+            let heartbeat = command(WORKER_HEADER, WORKER_HEARTBEAT);
+            self.outgoing
+                .push_back(prepend(heartbeat, vec![peer.clone().into()]));
+        }
+    }
+
+    /// Forgets a worker and queues its unfinished request again.
+    fn remove_worker(&mut self, peer: &PeerId) {
+        let worker = match self.workers.remove(peer) {
+            Some(worker) => worker,
+            None => return,
+        };
+        let service = self.services.entry(worker.service.clone()).or_default();
+        service.waiting.retain(|waiting| waiting != peer);
+        if let Some(request) = worker.request {
+            log::debug!("Requeuing request of worker {:?}", peer);
+            service.requests.push_front(request);
+            self.dispatch(&worker.service);
+        }
+    }
+
+    fn disconnect(&mut self, peer: PeerId) {
+        self.remove_worker(&peer);
+        self.outgoing.push_back(prepend(
+            command(WORKER_HEADER, WORKER_DISCONNECT),
+            vec![peer.into()],
+        ));
+    }
+}
+
+impl AsZmqSocket for MdpBroker {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
+
+/// Majordomo client, which sends requests to services through a broker.
+///
+/// The client has one request in flight at a time. If a request is cancelled, its reply may still
+/// arrive later. Replies for a different service are skipped, but a late reply for the same
+/// service would be taken as the reply of the next request. After a timeout set by
+/// [`with_timeout`](#method.with_timeout), a client created by [`connect`](#method.connect)
+/// replaces its socket, so that late replies are dropped. Other clients should be recreated after
+/// a request has timed out or has been cancelled.
+pub struct MdpClient {
+    socket: Dealer,
+    timeout: Option<Duration>,
+    // Context and endpoint of a socket created by `connect`.
+    endpoint: Option<(ContextRef, String)>,
+}
+
+impl MdpClient {
+    /// Creates a client which talks to a broker through the given DEALER socket.
+    pub fn new(socket: Dealer) -> Self {
+        Self {
+            socket,
+            timeout: None,
+            endpoint: None,
+        }
+    }
+
+    /// Creates a client on a DEALER socket which is connected to the broker at `endpoint`.
+    pub fn connect(context: &impl AsContext, endpoint: &str) -> Result<Self> {
+        let context = ContextRef::from_context(context);
+        let mut client = Self::new(connect_client(&context, endpoint)?);
+        client.endpoint = Some((context, endpoint.to_owned()));
+        Ok(client)
+    }
+
+    /// Sets the time to wait for the final reply of a request, which includes sending it.
+    ///
+    /// By default, requests wait until a worker of the service has replied.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends a request to `service` and waits for its final reply.
+    ///
+    /// Partial replies sent by the worker before the final one are skipped.
+    /// Returns [`TmqError::Timeout`] if the reply doesn't arrive within the timeout of the client.
+    pub async fn request<M: Into<Multipart>>(
+        &mut self,
+        service: &str,
+        request: M,
+    ) -> Result<Multipart> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.exchange(service, request.into()).await,
+        };
+        match tokio::time::timeout(timeout, self.exchange(service, request.into())).await {
+            Ok(result) => result,
+            Err(_) => {
+                if let Some((context, endpoint)) = &self.endpoint {
+                    self.socket = connect_client(context, endpoint)?;
+                }
+                Err(TmqError::Timeout)
+            }
+        }
+    }
+
+    async fn exchange(&mut self, service: &str, request: Multipart) -> Result<Multipart> {
+        let header = vec![
+            Message::from(CLIENT_HEADER),
+            (&[REQUEST][..]).into(),
+            service.into(),
+        ];
+        self.socket.send(prepend(request, header)).await?;
+
+        loop {
+            let mut reply = self.socket.next().await.ok_or(TmqError::Closed)??;
+            let command = pop_command(&mut reply, CLIENT_HEADER);
+            match reply.pop_front() {
+                Some(name) if &name[..] == service.as_bytes() => {}
+                Some(name) => {
+                    log::warn!("Skipping reply of service {:?}", name.as_str());
+                    continue;
+                }
+                None => return Err(TmqError::Protocol("MDP reply without service".into())),
+            }
+            match command {
+                Some(FINAL) => return Ok(reply),
+                Some(PARTIAL) => continue,
+                _ => return Err(TmqError::Protocol("invalid MDP reply".into())),
+            }
+        }
+    }
+
+    /// Returns the inner DEALER socket.
+    pub fn into_inner(self) -> Dealer {
+        self.socket
+    }
+}
+
+/// Connects a client socket, which drops pending requests when it is replaced after a timeout.
+fn connect_client(context: &ContextRef, endpoint: &str) -> Result<Dealer> {
+    dealer(context).set_linger(0).connect(endpoint)
+}
+
+impl AsZmqSocket for MdpClient {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
+
+/// Majordomo worker, a `Stream` of the requests for a service.
+///
+/// The worker registers itself with the broker and sends heartbeats while it is polled. If the
+/// broker hasn't sent anything for `liveness` heartbeat intervals, the worker reconnects with a new
+/// socket.
+///
+/// Every request is answered through its [`MdpRequest`] handle. The replies are sent the next time
+/// the worker is polled, so the worker should keep being polled after the last reply.
+///
+/// The broker also forgets workers which stop sending heartbeats while handling a request. Requests
+/// which take longer than `liveness` heartbeat intervals should be handled in a separate task
+/// while the worker keeps being polled.
+pub struct MdpWorker {
+    service: Vec<u8>,
+    socket: WorkerSocket,
+}
+
+impl MdpWorker {
+    /// Connects to the broker at `endpoint` and registers for `service`.
+    pub fn connect(context: &impl AsContext, endpoint: &str, service: &str) -> Result<Self> {
+        let socket = WorkerSocket::connect(
+            context,
+            endpoint,
+            || command(WORKER_HEADER, WORKER_HEARTBEAT),
+            HEARTBEAT_INTERVAL,
+            HEARTBEAT_LIVENESS,
+        )?;
+        let mut worker = Self {
+            service: service.as_bytes().to_vec(),
+            socket,
+        };
+        worker.ready();
+        Ok(worker)
+    }
+
+    /// Sets the heartbeat interval and the number of heartbeats which the broker can miss.
+    ///
+    /// The broker should use the same settings, see [`MdpBroker::with_heartbeat`].
+    pub fn with_heartbeat(mut self, interval: Duration, liveness: u32) -> Self {
+        self.socket.set_heartbeat(interval, liveness);
+        self
+    }
+
+    /// Returns the name of the service.
+    pub fn service(&self) -> &[u8] {
+        &self.service
+    }
+
+    fn ready(&mut self) {
+        let mut ready = command(WORKER_HEADER, WORKER_READY);
+        ready.push_back(Message::from(&self.service[..]));
+        self.socket.send(ready);
+    }
+
+    /// Replaces the socket after the broker has disconnected the worker or has gone away.
+    fn reconnect(&mut self) -> Result<()> {
+        self.socket.reconnect()?;
+        self.ready();
+        Ok(())
+    }
+}
+
+impl AsZmqSocket for MdpWorker {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
+
+impl Stream for MdpWorker {
+    type Item = Result<MdpRequest>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        'maintenance: loop {
+            match this.socket.poll_maintenance(cx) {
+                Ok(true) => {}
+                Ok(false) => match this.reconnect() {
+                    Ok(()) => continue,
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                },
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            loop {
+                let mut multipart = match ready!(this.socket.poll_recv(cx)) {
+                    Some(Ok(multipart)) => multipart,
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    None => return Poll::Ready(None),
+                };
+
+                match pop_command(&mut multipart, WORKER_HEADER) {
+                    Some(WORKER_REQUEST) => {
+                        let client = multipart.pop_front();
+                        let delimiter = multipart.pop_front();
+                        match (client, delimiter) {
+                            (Some(client), Some(delimiter)) if delimiter.is_empty() => {
+                                return Poll::Ready(Some(Ok(MdpRequest {
+                                    client,
+                                    body: multipart,
+                                    replies: this.socket.reply_sender(),
+                                })));
+                            }
+                            _ => log::warn!("Ignoring MDP request without client address"),
+                        }
+                    }
+                    Some(WORKER_HEARTBEAT) => {}
+                    Some(WORKER_DISCONNECT) => {
+                        if let Err(e) = this.reconnect() {
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                        continue 'maintenance;
+                    }
+                    _ => log::warn!("Ignoring invalid MDP message from broker"),
+                }
+            }
+        }
+    }
+}
+
+/// A request received by an [`MdpWorker`], with a handle for replying to it.
+pub struct MdpRequest {
+    client: Message,
+    body: Multipart,
+    replies: mpsc::UnboundedSender<Multipart>,
+}
+
+impl MdpRequest {
+    /// Returns the body of the request.
+    pub fn body(&self) -> &Multipart {
+        &self.body
+    }
+
+    /// Returns a mutable reference to the body of the request.
+    pub fn body_mut(&mut self) -> &mut Multipart {
+        &mut self.body
+    }
+
+    /// Returns the address of the client, as assigned by the broker.
+    pub fn client(&self) -> &[u8] {
+        &self.client
+    }
+
+    /// Sends a partial reply, which is followed by more replies.
+    pub fn partial<M: Into<Multipart>>(&self, reply: M) -> Result<()> {
+        self.send(WORKER_PARTIAL, reply.into())
+    }
+
+    /// Sends the final reply, after which the worker is ready for the next request.
+    pub fn reply<M: Into<Multipart>>(self, reply: M) -> Result<()> {
+        self.send(WORKER_FINAL, reply.into())
+    }
+
+    fn send(&self, command: u8, reply: Multipart) -> Result<()> {
+        let header = vec![
+            Message::from(WORKER_HEADER),
+            (&[command][..]).into(),
+            Message::from(&self.client[..]),
+            Message::new(),
+        ];
+        self.replies
+            .unbounded_send(prepend(reply, header))
+            .map_err(|_| TmqError::Closed)
+    }
+}
@@ -0,0 +1,175 @@
+//! Reliable request-reply patterns from the [zguide](https://zguide.zeromq.org/docs/chapter4/),
+//! built on tmq sockets.
+//!
+//! * [`lazy_pirate`]: REQ client which retries requests and fails over between servers.
+//! * [`majordomo`]: service-oriented broker, clients and workers speaking MDP/0.2.
+//! * [`paranoid_pirate`]: load balancing queue and workers which exchange heartbeats.
+
+pub mod lazy_pirate;
+pub mod majordomo;
+pub mod paranoid_pirate;
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, ready, Sink, StreamExt};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use crate::{
+    context::ContextRef, dealer, dealer::Dealer, socket::AsZmqSocket, AsContext, Message,
+    Multipart, Result,
+};
+
+/// Copies the frames of a multipart, which is kept for sending it again.
+fn copy(multipart: &Multipart) -> Multipart {
+    multipart
+        .iter()
+        .map(|frame| Message::from(&frame[..]))
+        .collect()
+}
+
+fn heartbeat_interval(period: Duration) -> Interval {
+    let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// DEALER socket of a worker, which exchanges heartbeats with its broker or queue and sends the
+/// replies of its requests.
+///
+/// Messages are only sent and heartbeats only counted while the socket is polled.
+struct WorkerSocket {
+    context: ContextRef,
+    endpoint: String,
+    socket: Dealer,
+    heartbeat: Interval,
+    // Creates the heartbeats sent to the broker or queue.
+    heartbeat_message: fn() -> Multipart,
+    liveness: u32,
+    // Remaining heartbeat intervals until the broker or queue is considered dead.
+    remaining: u32,
+    replies: mpsc::UnboundedReceiver<Multipart>,
+    reply_sender: mpsc::UnboundedSender<Multipart>,
+    outgoing: VecDeque<Multipart>,
+}
+
+impl WorkerSocket {
+    fn connect(
+        context: &impl AsContext,
+        endpoint: &str,
+        heartbeat_message: fn() -> Multipart,
+        interval: Duration,
+        liveness: u32,
+    ) -> Result<Self> {
+        let context = ContextRef::from_context(context);
+        let socket = dealer(&context).connect(endpoint)?;
+        let (reply_sender, replies) = mpsc::unbounded();
+        Ok(Self {
+            context,
+            endpoint: endpoint.to_owned(),
+            socket,
+            heartbeat: heartbeat_interval(interval),
+            heartbeat_message,
+            liveness,
+            remaining: liveness,
+            replies,
+            reply_sender,
+            outgoing: VecDeque::new(),
+        })
+    }
+
+    fn set_heartbeat(&mut self, interval: Duration, liveness: u32) {
+        self.heartbeat = heartbeat_interval(interval);
+        self.liveness = liveness;
+        self.remaining = liveness;
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns a sender for the replies of requests, which are sent by `poll_maintenance`.
+    fn reply_sender(&self) -> mpsc::UnboundedSender<Multipart> {
+        self.reply_sender.clone()
+    }
+
+    /// Queues a message, which is sent by `poll_maintenance`.
+    fn send(&mut self, multipart: Multipart) {
+        self.outgoing.push_back(multipart);
+    }
+
+    /// Replaces the socket, dropping the messages which haven't been sent yet.
+    fn reconnect(&mut self) -> Result<()> {
+        log::debug!("Worker reconnecting to {}", self.endpoint);
+        self.socket = dealer(&self.context).connect(&self.endpoint)?;
+        self.outgoing.clear();
+        self.restart_liveness();
+        Ok(())
+    }
+
+    /// Gives the broker or queue `liveness` full heartbeat intervals again.
+    fn restart_liveness(&mut self) {
+        self.remaining = self.liveness;
+        self.heartbeat.reset();
+    }
+
+    /// Queues the replies of requests, returns `true` if there were any.
+    fn poll_replies(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut replied = false;
+        while let Poll::Ready(Some(reply)) = self.replies.poll_next_unpin(cx) {
+            self.outgoing.push_back(reply);
+            replied = true;
+        }
+        replied
+    }
+
+    /// Queues replies and heartbeats and sends the queued messages.
+    ///
+    /// Returns `false` once the broker or queue hasn't sent anything for `liveness` heartbeat
+    /// intervals.
+    fn poll_maintenance(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        self.poll_replies(cx);
+        while self.heartbeat.poll_tick(cx).is_ready() {
+            self.remaining = self.remaining.saturating_sub(1);
+            if self.remaining == 0 {
+                return Ok(false);
+            }
+            self.outgoing.push_back((self.heartbeat_message)());
+        }
+        if let Poll::Ready(Err(e)) = self.poll_send(cx) {
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while !self.outgoing.is_empty() {
+            ready!(Sink::<Multipart>::poll_ready(
+                Pin::new(&mut self.socket),
+                cx
+            ))?;
+            let multipart = self.outgoing.pop_front().unwrap();
+            Pin::new(&mut self.socket).start_send(multipart)?;
+        }
+        Sink::<Multipart>::poll_flush(Pin::new(&mut self.socket), cx)
+    }
+
+    /// Receives the next message of the broker or queue.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Multipart>>> {
+        let message = ready!(self.socket.poll_next_unpin(cx));
+        if let Some(Ok(_)) = message {
+            self.remaining = self.liveness;
+        }
+        Poll::Ready(message)
+    }
+}
+
+impl AsZmqSocket for WorkerSocket {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
@@ -0,0 +1,367 @@
+//! Paranoid Pirate: a load balancing queue and workers which watch each other with heartbeats.
+//!
+//! Clients (REQ, DEALER or [`LazyPirateClient`](super::lazy_pirate::LazyPirateClient)) send
+//! requests to the frontend ROUTER of a [`PirateQueue`], which hands them to the least recently
+//! used ready worker on its backend ROUTER. [`PirateWorker`]s connect to the backend with DEALER
+//! sockets and announce themselves with a READY message.
+//!
+//! The queue and the idle workers exchange heartbeats:
+//!
+//! * the queue evicts workers which haven't sent anything for `liveness` heartbeat intervals,
+//! * a worker which hasn't heard from the queue for `liveness` heartbeat intervals considers it
+//!   dead, waits for the reconnect interval and reconnects with a new socket. The reconnect
+//!   interval doubles after every reconnect up to a maximum, and is reset once the queue answers.
+//!
+//! READY is a single frame `0x01` and a heartbeat is a single frame `0x02`, like in the zguide.
+//!
+//! ## Usage Example
+//!
+//! ```rust,no_run
+//! use futures::StreamExt;
+//! use tmq::{
+//!     patterns::paranoid_pirate::{PirateQueue, PirateWorker},
+//!     Context, Result,
+//! };
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let ctx = Context::new();
+//!     let queue = PirateQueue::bind(&ctx, "tcp://127.0.0.1:5555", "tcp://127.0.0.1:5556")?;
+//!     tokio::spawn(queue.run());
+//!
+//!     let mut worker = PirateWorker::connect(&ctx, "tcp://127.0.0.1:5556")?;
+//!     while let Some(request) = worker.next().await {
+//!         let mut request = request?;
+//!         let body = std::mem::take(request.body_mut());
+//!         request.reply(body)?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, future::poll_fn, ready, SinkExt, Stream, StreamExt};
+use tokio::time::{Instant, Interval, MissedTickBehavior, Sleep};
+
+use super::WorkerSocket;
+use crate::{
+    message::split_envelope,
+    router,
+    router::{PeerId, Router},
+    socket::AsZmqSocket,
+    AsContext, Message, Multipart, Result, TmqError,
+};
+
+/// Frame which a worker sends when it's ready for requests.
+pub const READY: &[u8] = &[0x01];
+/// Frame of the heartbeats between the queue and workers.
+pub const HEARTBEAT: &[u8] = &[0x02];
+
+/// Default interval of the heartbeats between the queue and workers.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Default number of heartbeats which can be missed before the other side is considered dead.
+pub const HEARTBEAT_LIVENESS: u32 = 3;
+/// Default interval before the first reconnect of a worker.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Default maximum interval between reconnects of a worker.
+pub const RECONNECT_INTERVAL_MAX: Duration = Duration::from_secs(32);
+
+/// Load balancing queue between clients on a frontend ROUTER and workers on a backend ROUTER.
+///
+/// Requests are only read from the frontend while a worker is ready, so they queue up in the
+/// frontend socket otherwise. Every message of a worker, including heartbeats, marks it as
+/// ready.
+pub struct PirateQueue {
+    frontend: Router,
+    backend: Router,
+    // Ready workers, the least recently used first.
+    workers: VecDeque<(PeerId, Instant)>,
+    heartbeat_interval: Duration,
+    liveness: u32,
+}
+
+impl PirateQueue {
+    /// Creates a queue which serves clients on `frontend` and workers on `backend`.
+    pub fn new(frontend: Router, backend: Router) -> Self {
+        Self {
+            frontend,
+            backend,
+            workers: VecDeque::new(),
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+            liveness: HEARTBEAT_LIVENESS,
+        }
+    }
+
+    /// Creates a queue on two ROUTER sockets bound to the given endpoints.
+    pub fn bind(context: &impl AsContext, frontend: &str, backend: &str) -> Result<Self> {
+        Ok(Self::new(
+            router(context).bind(frontend)?,
+            router(context).bind(backend)?,
+        ))
+    }
+
+    /// Sets the heartbeat interval and the number of heartbeats which a worker can miss.
+    ///
+    /// The workers should use the same settings, see [`PirateWorker::with_heartbeat`].
+    pub fn with_heartbeat(mut self, interval: Duration, liveness: u32) -> Self {
+        self.heartbeat_interval = interval;
+        self.liveness = liveness;
+        self
+    }
+
+    /// Runs the queue until one of its sockets fails.
+    pub async fn run(mut self) -> Result<()> {
+        let mut heartbeat = tokio::time::interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            match poll_fn(|cx| self.poll_event(cx, &mut heartbeat)).await? {
+                Event::Worker(message) => self.handle_worker(message).await?,
+                Event::Client(mut message) => {
+                    let (worker, _) = self.workers.pop_front().unwrap();
+                    message.push_front(worker.into());
+                    self.backend.send(message).await?;
+                }
+                Event::Heartbeat => self.heartbeat().await?,
+            }
+        }
+    }
+
+    fn poll_event(
+        &mut self,
+        cx: &mut Context<'_>,
+        heartbeat: &mut Interval,
+    ) -> Poll<Result<Event>> {
+        if heartbeat.poll_tick(cx).is_ready() {
+            return Poll::Ready(Ok(Event::Heartbeat));
+        }
+        if let Poll::Ready(message) = self.backend.poll_next_unpin(cx) {
+            let message = message.ok_or(TmqError::Closed)??;
+            return Poll::Ready(Ok(Event::Worker(message)));
+        }
+        if !self.workers.is_empty() {
+            if let Poll::Ready(message) = self.frontend.poll_next_unpin(cx) {
+                let message = message.ok_or(TmqError::Closed)??;
+                return Poll::Ready(Ok(Event::Client(message)));
+            }
+        }
+        Poll::Pending
+    }
+
+    async fn handle_worker(&mut self, mut message: Multipart) -> Result<()> {
+        let worker = match message.pop_front() {
+            Some(worker) => PeerId::from(&worker),
+            None => return Ok(()),
+        };
+        self.workers.retain(|(ready, _)| *ready != worker);
+        let expiry = Instant::now() + self.heartbeat_interval * self.liveness;
+        self.workers.push_back((worker.clone(), expiry));
+
+        if message.len() > 1 {
+            return self.frontend.send(message).await;
+        }
+        match message.0.front() {
+            Some(frame) if &frame[..] == READY || &frame[..] == HEARTBEAT => {}
+            _ => log::warn!("Invalid message from worker {:?}", worker),
+        }
+        Ok(())
+    }
+
+    /// Sends heartbeats to the ready workers and evicts the expired ones.
+    async fn heartbeat(&mut self) -> Result<()> {
+        let now = Instant::now();
+        self.workers.retain(|(worker, expiry)| {
+            if *expiry <= now {
+                log::debug!("Worker {:?} expired", worker);
+            }
+            *expiry > now
+        });
+        for (worker, _) in &self.workers {
+            let heartbeat = vec![Message::from(worker.clone()), HEARTBEAT.into()];
+            self.backend.send(heartbeat).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of workers which are ready for a request.
+    pub fn ready_workers(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+enum Event {
+    Worker(Multipart),
+    Client(Multipart),
+    Heartbeat,
+}
+
+/// Worker of a [`PirateQueue`], a `Stream` of requests.
+///
+/// The worker announces itself with READY and sends heartbeats while it is polled. If the queue
+/// hasn't sent anything for `liveness` heartbeat intervals, the worker waits for the reconnect
+/// interval and reconnects with a new socket.
+///
+/// Every request is answered through its [`PirateRequest`] handle. The replies are sent the next
+/// time the worker is polled, so the worker should keep being polled after the last reply.
+pub struct PirateWorker {
+    socket: WorkerSocket,
+    reconnect_interval: Duration,
+    reconnect_interval_init: Duration,
+    reconnect_interval_max: Duration,
+    // Running while the worker waits before reconnecting.
+    backoff: Option<Pin<Box<Sleep>>>,
+}
+
+impl PirateWorker {
+    /// Connects to the backend of a queue at `endpoint` and announces the worker as ready.
+    pub fn connect(context: &impl AsContext, endpoint: &str) -> Result<Self> {
+        let mut socket = WorkerSocket::connect(
+            context,
+            endpoint,
+            || Multipart::from(vec![HEARTBEAT]),
+            HEARTBEAT_INTERVAL,
+            HEARTBEAT_LIVENESS,
+        )?;
+        socket.send(Multipart::from(vec![READY]));
+        Ok(Self {
+            socket,
+            reconnect_interval: RECONNECT_INTERVAL,
+            reconnect_interval_init: RECONNECT_INTERVAL,
+            reconnect_interval_max: RECONNECT_INTERVAL_MAX,
+            backoff: None,
+        })
+    }
+
+    /// Sets the heartbeat interval and the number of heartbeats which the queue can miss.
+    ///
+    /// The queue should use the same settings, see [`PirateQueue::with_heartbeat`].
+    pub fn with_heartbeat(mut self, interval: Duration, liveness: u32) -> Self {
+        self.socket.set_heartbeat(interval, liveness);
+        self
+    }
+
+    /// Sets the interval before the first reconnect and the maximum interval, up to which it
+    /// doubles after every further reconnect.
+    pub fn with_reconnect_interval(mut self, initial: Duration, max: Duration) -> Self {
+        self.reconnect_interval = initial;
+        self.reconnect_interval_init = initial;
+        self.reconnect_interval_max = max;
+        self
+    }
+
+    /// Replaces the socket after the queue has gone away.
+    fn reconnect(&mut self) -> Result<()> {
+        self.socket.reconnect()?;
+        self.socket.send(Multipart::from(vec![READY]));
+        self.reconnect_interval = (self.reconnect_interval * 2).min(self.reconnect_interval_max);
+        Ok(())
+    }
+
+    /// Handles heartbeats, reconnects and replies, returns `false` while waiting to reconnect.
+    fn poll_maintenance(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        if let Some(backoff) = &mut self.backoff {
+            if backoff.as_mut().poll(cx).is_pending() {
+                return Ok(false);
+            }
+            self.backoff = None;
+            self.reconnect()?;
+        }
+
+        // The queue only sends heartbeats to ready workers, so the time spent on a request
+        // doesn't count.
+        if self.socket.poll_replies(cx) {
+            self.socket.restart_liveness();
+        }
+        if !self.socket.poll_maintenance(cx)? {
+            log::warn!(
+                "Queue at {} is unreachable, reconnecting in {:?}",
+                self.socket.endpoint(),
+                self.reconnect_interval
+            );
+            self.backoff = Some(Box::pin(tokio::time::sleep(self.reconnect_interval)));
+            return self.poll_maintenance(cx);
+        }
+        Ok(true)
+    }
+}
+
+impl AsZmqSocket for PirateWorker {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
+
+impl Stream for PirateWorker {
+    type Item = Result<PirateRequest>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        match this.poll_maintenance(cx) {
+            Ok(true) => {}
+            Ok(false) => return Poll::Pending,
+            Err(e) => return Poll::Ready(Some(Err(e))),
+        }
+
+        loop {
+            let mut multipart = match ready!(this.socket.poll_recv(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            this.reconnect_interval = this.reconnect_interval_init;
+
+            if multipart.len() == 1 {
+                if &multipart[0][..] != HEARTBEAT {
+                    log::warn!("Ignoring invalid message from queue");
+                }
+                continue;
+            }
+
+            // The envelope is the client identity added by the queue, followed by the delimiter
+            // of REQ clients, so that empty frames of other clients stay in the body.
+            let envelope = split_envelope(&mut multipart, false);
+            return Poll::Ready(Some(Ok(PirateRequest {
+                envelope,
+                body: multipart,
+                replies: this.socket.reply_sender(),
+            })));
+        }
+    }
+}
+
+/// A request received by a [`PirateWorker`], with a handle for replying to it.
+pub struct PirateRequest {
+    envelope: Multipart,
+    body: Multipart,
+    replies: mpsc::UnboundedSender<Multipart>,
+}
+
+impl PirateRequest {
+    /// Returns the body of the request.
+    pub fn body(&self) -> &Multipart {
+        &self.body
+    }
+
+    /// Returns a mutable reference to the body of the request.
+    pub fn body_mut(&mut self) -> &mut Multipart {
+        &mut self.body
+    }
+
+    /// Sends the reply to the client through the queue.
+    pub fn reply<M: Into<Multipart>>(self, reply: M) -> Result<()> {
+        let mut reply = reply.into();
+        for frame in self.envelope.into_iter().rev() {
+            reply.push_front(frame);
+        }
+        self.replies
+            .unbounded_send(reply)
+            .map_err(|_| TmqError::Closed)
+    }
+}
@@ -1,29 +1,107 @@
-use futures::ready;
+use futures::{ready, task::AtomicWaker};
 
-use crate::error::TmqError::InterruptedSend;
+use crate::context::OwnedSocket;
+use crate::message::Frames;
 use crate::socket::{AsZmqSocket, SocketWrapper};
 use crate::{Multipart, Result};
 use std::{
     collections::VecDeque,
-    task::{Context, Poll},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
 };
-use tokio::io::unix::AsyncFd;
+use tokio::{io::unix::AsyncFd, task::coop};
 use zmq::Socket;
 
 /// Implements functions for asynchronous reading and writing of multipart messages.
-pub(crate) struct ZmqPoller(AsyncFd<SocketWrapper>);
+///
+/// The ZMQ file descriptor is edge triggered and only signals that the socket's events may have
+/// changed, for both directions at once. Every operation on the socket (including reading
+/// `ZMQ_EVENTS`) may consume such a signal, so after each operation the events are checked for
+/// the other direction as well and a task waiting for it is woken if it became ready.
+pub(crate) struct ZmqPoller {
+    fd: AsyncFd<SocketWrapper>,
+    wakers: Arc<Wakers>,
+    waker: Waker,
+}
 
 impl ZmqPoller {
     #[inline]
-    pub(crate) fn from_zmq_socket(socket: zmq::Socket) -> Result<Self> {
-        Ok(Self(AsyncFd::new(SocketWrapper::new(socket)?)?))
+    pub(crate) fn from_zmq_socket(socket: OwnedSocket) -> Result<Self> {
+        let wakers = Arc::new(Wakers::default());
+        Ok(Self {
+            fd: AsyncFd::new(SocketWrapper::new(socket)?)?,
+            waker: Waker::from(wakers.clone()),
+            wakers,
+        })
+    }
+}
+
+impl ZmqPoller {
+    pub(crate) fn get_socket_mut(&mut self) -> &mut OwnedSocket {
+        &mut self.fd.get_mut().socket
     }
 }
 
 impl AsZmqSocket for ZmqPoller {
     #[inline]
     fn get_socket(&self) -> &Socket {
-        &self.0.get_ref().socket
+        &self.fd.get_ref().socket
+    }
+}
+
+/// A task waiting for one direction of the socket.
+#[derive(Default)]
+struct Waiter {
+    waker: AtomicWaker,
+    waiting: AtomicBool,
+}
+
+impl Waiter {
+    fn register(&self, waker: &Waker) {
+        self.waker.register(waker);
+        self.waiting.store(true, Ordering::SeqCst);
+    }
+
+    fn done(&self) {
+        self.waiting.store(false, Ordering::SeqCst);
+    }
+
+    fn is_waiting(&self) -> bool {
+        self.waiting.load(Ordering::SeqCst)
+    }
+}
+
+/// Wakers of the tasks waiting to receive from and send to the socket.
+///
+/// A signal of the ZMQ file descriptor is forwarded to both, each of which then re-checks
+/// `ZMQ_EVENTS` for its own direction.
+#[derive(Default)]
+struct Wakers {
+    read: Waiter,
+    write: Waiter,
+}
+
+impl Wakers {
+    fn get(&self, event: zmq::PollEvents) -> &Waiter {
+        if event == zmq::POLLIN {
+            &self.read
+        } else {
+            &self.write
+        }
+    }
+}
+
+impl Wake for Wakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.read.waker.wake();
+        self.write.waker.wake();
     }
 }
 
@@ -33,49 +111,81 @@ impl ZmqPoller {
     /// If there is any message in the buffer, it will be returned right away.
     /// If not, a batch of messages up to the capacity of the buffer will be read from the socket.
     ///
-    /// If nothing was received, the read flag is cleared.
+    /// Every returned multipart consumes Tokio's cooperative budget, see
+    /// [`multipart_recv`](#method.multipart_recv).
     pub(crate) fn multipart_recv_buffered(
         &self,
         cx: &mut Context<'_>,
         read_buffer: &mut ReceiverBuffer,
     ) -> Poll<Result<Multipart>> {
-        if read_buffer.is_empty() {
-            ready!(self.multipart_poll_read_ready(cx))?;
+        let coop = ready!(coop::poll_proceed(cx));
+        let result = ready!(self.poll_recv_buffered(cx, read_buffer));
+        coop.made_progress();
+        Poll::Ready(result)
+    }
 
-            let mut buffer = Multipart::default();
-            loop {
-                let mut msg = zmq::Message::new();
-                match self.get_socket().recv(&mut msg, zmq::DONTWAIT) {
-                    Ok(_) => {
-                        let more = msg.get_more();
-                        buffer.push_back(msg);
-                        if !more {
-                            read_buffer.push_back(buffer);
-                            if read_buffer.is_full() {
-                                break Poll::Ready(Ok(read_buffer.pop_front().unwrap()));
-                            }
+    /// Receives through `read_buffer` if the socket is buffered, or one multipart at a time
+    /// otherwise.
+    pub(crate) fn multipart_recv_with(
+        &self,
+        cx: &mut Context<'_>,
+        read_buffer: &mut Option<ReceiverBuffer>,
+    ) -> Poll<Result<Multipart>> {
+        match read_buffer {
+            Some(read_buffer) => self.multipart_recv_buffered(cx, read_buffer),
+            None => self.multipart_recv(cx),
+        }
+    }
 
-                            buffer = Multipart::default();
-                        }
-                    }
-                    Err(zmq::Error::EAGAIN) => {
-                        if !buffer.is_empty() {
-                            read_buffer.push_back(buffer);
-                        }
-                        self.clear_read_ready(cx)?;
+    fn poll_recv_buffered(
+        &self,
+        cx: &mut Context<'_>,
+        read_buffer: &mut ReceiverBuffer,
+    ) -> Poll<Result<Multipart>> {
+        loop {
+            if let Some(multipart) = read_buffer.pop_front() {
+                return Poll::Ready(Ok(multipart));
+            }
 
-                        if read_buffer.is_empty() {
-                            break Poll::Pending;
-                        } else {
-                            break Poll::Ready(Ok(read_buffer.pop_front().unwrap()));
-                        }
+            ready!(self.multipart_poll_read_ready(cx))?;
+            let received = self.recv_batch(read_buffer);
+            self.wake_ready(zmq::POLLOUT)?;
+            let received = received?;
+            read_buffer.adapt(received);
+            if received == 0 {
+                // The socket reported POLLIN, but there was no message.
+                ready!(self.poll_signal(cx, zmq::POLLIN))?;
+            }
+        }
+    }
+
+    /// Receive multiparts into the buffer until it is full or the socket has no more messages.
+    /// Returns the number of received multiparts.
+    fn recv_batch(&self, read_buffer: &mut ReceiverBuffer) -> Result<usize> {
+        let mut buffer = Multipart::default();
+        let mut received = 0;
+        while !read_buffer.is_full() {
+            let mut msg = zmq::Message::new();
+            match self.get_socket().recv(&mut msg, zmq::DONTWAIT) {
+                Ok(_) => {
+                    let more = msg.get_more();
+                    buffer.push_back(msg);
+                    if !more {
+                        read_buffer.push_back(std::mem::take(&mut buffer));
+                        received += 1;
                     }
-                    Err(e) => break Poll::Ready(Err(e.into())),
                 }
+                Err(zmq::Error::EAGAIN) => {
+                    if !buffer.is_empty() {
+                        read_buffer.push_back(buffer);
+                        received += 1;
+                    }
+                    break;
+                }
+                Err(e) => return Err(e.into()),
             }
-        } else {
-            Poll::Ready(Ok(read_buffer.pop_front().unwrap()))
         }
+        Ok(received)
     }
 
     /// Attempt to receive a Multipart message from a ZeroMQ socket.
@@ -84,43 +194,60 @@ impl ZmqPoller {
     /// [http://zguide.zeromq.org/page:all#Multipart-Messages], when one part of a multipart message
     /// has been received, all the others are already available as well).
     ///
-    /// If nothing was received, the read flag is cleared.
+    /// Every received multipart consumes Tokio's cooperative budget. Once the budget of the
+    /// task is used up, `Poll::Pending` is returned and the task is rescheduled, so that a
+    /// flooded socket doesn't starve the other tasks of the runtime.
     pub(crate) fn multipart_recv(&self, cx: &mut Context<'_>) -> Poll<Result<Multipart>> {
-        ready!(self.multipart_poll_read_ready(cx))?;
+        let coop = ready!(coop::poll_proceed(cx));
+        let result = ready!(self.poll_recv(cx));
+        coop.made_progress();
+        Poll::Ready(result)
+    }
 
-        let mut buffer = Multipart::default();
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<Multipart>> {
         loop {
-            let mut msg = zmq::Message::new();
-            match self.get_socket().recv(&mut msg, zmq::DONTWAIT) {
-                Ok(_) => {
-                    let more = msg.get_more();
-                    buffer.push_back(msg);
-                    if !more {
+            ready!(self.multipart_poll_read_ready(cx))?;
+
+            let mut buffer = Multipart::default();
+            loop {
+                let mut msg = zmq::Message::new();
+                match self.get_socket().recv(&mut msg, zmq::DONTWAIT) {
+                    Ok(_) => {
+                        let more = msg.get_more();
+                        buffer.push_back(msg);
+                        if !more {
+                            break;
+                        }
+                    }
+                    Err(zmq::Error::EAGAIN) => {
+                        assert!(buffer.is_empty());
+                        // Spurious readiness, e.g. another task has received the message.
+                        log::trace!("EAGAIN during first message read");
                         break;
                     }
+                    Err(e) => return Poll::Ready(Err(e.into())),
                 }
-                Err(zmq::Error::EAGAIN) => {
-                    assert!(buffer.is_empty());
-                    log::warn!("EAGAIN during first message read");
-                    self.clear_read_ready(cx)?;
-                    return Poll::Pending;
-                }
-                Err(e) => return Poll::Ready(Err(e.into())),
             }
-        }
 
-        assert!(!buffer.is_empty());
-        Poll::Ready(Ok(buffer))
+            if buffer.is_empty() {
+                ready!(self.poll_signal(cx, zmq::POLLIN))?;
+            } else {
+                self.wake_ready(zmq::POLLOUT)?;
+                return Poll::Ready(Ok(buffer));
+            }
+        }
     }
 
-    /// Attempt to send a multipart message.
+    /// Attempt to send the frames of a multipart message.
+    ///
+    /// Sends as many frames as possible. The frames which could not be sent yet stay in the
+    /// buffer, so a partially sent multipart can be resumed by calling this function again.
+    /// Returns `true` if at least one frame has been sent.
     ///
-    /// Sending the whole message at once may not be possible.
-    /// If the function returns `Poll::Ready(Ok(()))`, the whole message has been sent.
-    /// If the function returns `Poll::Pending`, the remaining message in the buffer should be
-    /// attempted to be written the next time the socket is polled.
-    pub(crate) fn multipart_send(&self, buffer: &mut Multipart) -> Poll<Result<()>> {
-        let len = buffer.len();
+    /// If sending a frame fails with an error other than `EAGAIN`, the rest of the multipart is
+    /// dropped.
+    fn multipart_send(&self, buffer: &mut Frames) -> Result<bool> {
+        let mut progress = false;
 
         while let Some(msg) = buffer.pop_front() {
             let mut flags = zmq::DONTWAIT;
@@ -128,38 +255,85 @@ impl ZmqPoller {
                 flags |= zmq::SNDMORE;
             }
 
-            match self.get_socket().send(&*msg, flags) {
-                Ok(_) => {}
+            match self.fd.get_ref().send_frame(&msg, flags) {
+                Ok(_) => progress = true,
                 Err(zmq::Error::EAGAIN) => {
                     buffer.push_front(msg);
-
-                    // If this was not the first message, we return a special error.
-                    if buffer.len() != len {
-                        return Poll::Ready(Err(InterruptedSend));
-                    }
-                    return Poll::Pending;
+                    break;
+                }
+                Err(e) => {
+                    // Drop the rest of the message, so that it isn't sent on its own later.
+                    buffer.clear();
+                    return Err(e.into());
                 }
-                Err(e) => return Poll::Ready(Err(e.into())),
             }
         }
 
-        Poll::Ready(Ok(()))
+        Ok(progress)
     }
 
     /// Attempt to flush the message buffer.
+    ///
+    /// Once the first frame of a multipart has been sent, the remaining frames stay in the buffer
+    /// until they are sent as well, so a multipart is never left unfinished.
     /// If the buffer cannot be fully flushed, `Poll::Pending` will be returned and a wakeup
     /// will be scheduled the next time there is an event on the ZMQ socket.
     pub(crate) fn multipart_flush(
         &self,
         cx: &mut Context<'_>,
-        buffer: &mut Multipart,
+        buffer: &mut Frames,
     ) -> Poll<Result<()>> {
         while !buffer.is_empty() {
             ready!(self.multipart_poll_write_ready(cx))?;
-            ready!(self.multipart_send(buffer))?;
+            let sent = self.multipart_send(buffer);
+            self.wake_ready(zmq::POLLIN)?;
+            if !sent? {
+                // The socket reported POLLOUT, but couldn't take a frame.
+                ready!(self.poll_signal(cx, zmq::POLLOUT))?;
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Attempt to send all queued multiparts.
+    ///
+    /// After the socket reports that it's writable, multiparts are sent one after another until
+    /// the queue is empty or the socket is full. A partially sent multipart stays at the front of
+    /// the queue and is finished first on the next call.
+    /// If sending fails, the failed multipart is removed from the queue and the rest is kept.
+    pub(crate) fn multipart_flush_queue(
+        &self,
+        cx: &mut Context<'_>,
+        queue: &mut VecDeque<Frames>,
+    ) -> Poll<Result<()>> {
+        while !queue.is_empty() {
+            ready!(self.multipart_poll_write_ready(cx))?;
+
+            let mut progress = false;
+            while let Some(multipart) = queue.front_mut() {
+                match self.multipart_send(multipart) {
+                    Ok(sent) => progress |= sent,
+                    Err(e) => {
+                        queue.pop_front();
+                        self.wake_ready(zmq::POLLIN)?;
+                        return Poll::Ready(Err(e));
+                    }
+                }
+                if !multipart.is_empty() {
+                    // The socket is full.
+                    break;
+                }
+                queue.pop_front();
+            }
+            self.wake_ready(zmq::POLLIN)?;
+
+            if !progress && !queue.is_empty() {
+                // The socket reported POLLOUT, but couldn't take a frame.
+                ready!(self.poll_signal(cx, zmq::POLLOUT))?;
+            }
         }
 
-        assert!(buffer.is_empty());
         Poll::Ready(Ok(()))
     }
 
@@ -176,27 +350,76 @@ impl ZmqPoller {
     }
 
     fn multipart_poll(&self, cx: &mut Context<'_>, event: zmq::PollEvents) -> Poll<Result<()>> {
-        let events = self.get_socket().get_events()?;
-        if events.contains(event) {
-            Poll::Ready(Ok(()))
+        let waiter = self.wakers.get(event);
+        waiter.register(cx.waker());
+        let mut fd_cx = Context::from_waker(&self.waker);
+
+        loop {
+            let events = self.get_socket().get_events()?;
+            self.wake_other(event, events);
+            if events.contains(event) {
+                waiter.done();
+                return Poll::Ready(Ok(()));
+            }
+
+            // The events may have changed between reading them and the last signal of the
+            // file descriptor, so they are read again after clearing its readiness.
+            match self.fd.poll_read_ready(&mut fd_cx)? {
+                Poll::Ready(mut guard) => guard.clear_ready(),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Clears the pending signal of the file descriptor, or schedules a wakeup on the next one.
+    ///
+    /// Used when the socket reports being ready for the given direction, but the operation
+    /// couldn't make progress (e.g. a ROUTER socket sending to a peer whose queue is full).
+    /// If a signal has been cleared, `Poll::Ready` is returned and the operation must be retried,
+    /// as the socket's state may have changed since it failed.
+    fn poll_signal(&self, cx: &mut Context<'_>, event: zmq::PollEvents) -> Poll<Result<()>> {
+        self.wakers.get(event).register(cx.waker());
+        let mut fd_cx = Context::from_waker(&self.waker);
+
+        let mut guard = ready!(self.fd.poll_read_ready(&mut fd_cx))?;
+        guard.clear_ready();
+        Poll::Ready(Ok(()))
+    }
+
+    /// Wakes the task waiting for the direction other than `event`, if it is ready according
+    /// to `events`.
+    fn wake_other(&self, event: zmq::PollEvents, events: zmq::PollEvents) {
+        let other = if event == zmq::POLLIN {
+            zmq::POLLOUT
         } else {
-            self.clear_read_ready(cx)?;
-            Poll::Pending
+            zmq::POLLIN
+        };
+        let waiter = self.wakers.get(other);
+        if waiter.is_waiting() && events.contains(other) {
+            waiter.waker.wake();
         }
     }
 
-    fn clear_read_ready(&self, cx: &mut Context<'_>) -> Result<()> {
-        if let Poll::Ready(mut guard) = self.0.poll_read_ready(cx)? {
-            guard.clear_ready();
-            cx.waker().wake_by_ref();
+    /// Wakes the task waiting for `event`, if there is one and the socket is ready for it.
+    ///
+    /// Sending or receiving may consume the signal of the file descriptor that this task
+    /// relies on.
+    fn wake_ready(&self, event: zmq::PollEvents) -> Result<()> {
+        let waiter = self.wakers.get(event);
+        if waiter.is_waiting() && self.get_socket().get_events()?.contains(event) {
+            waiter.waker.wake();
         }
         Ok(())
     }
 }
 
 /// Buffer used by receiver implementations to hold multiparts.
+///
+/// In adaptive mode, the batch size follows the observed burst length: it's doubled when
+/// a batch fills the buffer, and halved when a batch uses at most half of it.
 pub(crate) struct ReceiverBuffer {
     capacity: usize,
+    limits: Option<(usize, usize)>,
     buffer: VecDeque<Multipart>,
 }
 
@@ -204,12 +427,32 @@ impl ReceiverBuffer {
     pub(crate) fn new(capacity: usize) -> Self {
         assert!(capacity > 0);
         let buffer = VecDeque::with_capacity(capacity);
-        Self { capacity, buffer }
+        Self {
+            capacity,
+            limits: None,
+            buffer,
+        }
+    }
+
+    pub(crate) fn adaptive(min: usize, max: usize) -> Self {
+        assert!(min > 0 && min <= max);
+        Self {
+            limits: Some((min, max)),
+            ..Self::new(min)
+        }
+    }
+
+    /// Keeps the multiparts which have been received into `previous`, but not returned yet.
+    pub(crate) fn with_pending(mut self, previous: Option<ReceiverBuffer>) -> Self {
+        if let Some(previous) = previous {
+            self.buffer.extend(previous.buffer);
+        }
+        self
     }
 
     #[inline]
-    pub(crate) fn is_empty(&self) -> bool {
-        self.buffer.is_empty()
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
     }
 
     #[inline]
@@ -226,4 +469,15 @@ impl ReceiverBuffer {
     pub(crate) fn push_back(&mut self, item: Multipart) {
         self.buffer.push_back(item)
     }
+
+    /// Adjusts the batch size after a batch of `received` multiparts has been read.
+    fn adapt(&mut self, received: usize) {
+        if let Some((min, max)) = self.limits {
+            if received >= self.capacity {
+                self.capacity = (self.capacity * 2).min(max);
+            } else if received * 2 <= self.capacity {
+                self.capacity = (self.capacity / 2).max(min);
+            }
+        }
+    }
 }
@@ -0,0 +1,365 @@
+//! Asynchronous counterpart of [`zmq::proxy`](https://docs.rs/zmq/latest/zmq/fn.proxy.html).
+//!
+//! `zmq::proxy` blocks the calling thread and requires raw ZMQ sockets.
+//! The proxy in this module runs on Tokio and works with any pair of tmq sockets which are
+//! both a `Stream` and a `Sink` of multiparts, such as ROUTER/DEALER.
+//! See [`Proxy`] for the capture and control options.
+//!
+//! ## Usage Example
+//!
+//! ```rust,no_run
+//! use tmq::{dealer, proxy::proxy, router, Context, Result};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let ctx = Context::new();
+//!     let frontend = router(&ctx).bind("tcp://127.0.0.1:5555")?;
+//!     let backend = dealer(&ctx).bind("tcp://127.0.0.1:5556")?;
+//!
+//!     proxy(frontend, backend).await
+//! }
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{future::poll_fn, Sink, SinkExt, Stream};
+
+use crate::{Message, Multipart, Result, TmqError};
+
+/// A socket which can be used as a side of a [`Proxy`].
+///
+/// Implemented for every type which is both a `Stream` and a `Sink` of multiparts.
+pub trait ProxySocket:
+    Stream<Item = Result<Multipart>> + Sink<Multipart, Error = TmqError>
+{
+}
+
+impl<T> ProxySocket for T where
+    T: Stream<Item = Result<Multipart>> + Sink<Multipart, Error = TmqError>
+{
+}
+
+type CaptureSink = Pin<Box<dyn Sink<Multipart, Error = TmqError> + Send>>;
+type ControlSocket = Pin<Box<dyn ProxySocket + Send>>;
+
+/// Forwards messages between `frontend` and `backend` until one of them is closed or fails.
+///
+/// This is a shortcut for `Proxy::new(frontend, backend).run()`.
+pub async fn proxy<F, B>(frontend: F, backend: B) -> Result<()>
+where
+    F: ProxySocket + Unpin,
+    B: ProxySocket + Unpin,
+{
+    Proxy::new(frontend, backend).run().await
+}
+
+/// Command which can be sent to the control socket of a [`Proxy`].
+///
+/// The commands use the same wire format as `zmq_proxy_steerable`: a single frame containing
+/// `PAUSE`, `RESUME`, `TERMINATE` or `STATISTICS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyCommand {
+    /// Stop forwarding messages until `Resume` is received.
+    Pause,
+    /// Resume forwarding messages.
+    Resume,
+    /// Stop the proxy.
+    Terminate,
+    /// Reply with the current [`ProxyStatistics`] on the control socket.
+    Statistics,
+}
+
+impl ProxyCommand {
+    /// Parses a command frame, returning [`None`] for unknown commands.
+    pub fn from_message(message: &Message) -> Option<Self> {
+        match &message[..] {
+            b"PAUSE" => Some(ProxyCommand::Pause),
+            b"RESUME" => Some(ProxyCommand::Resume),
+            b"TERMINATE" => Some(ProxyCommand::Terminate),
+            b"STATISTICS" => Some(ProxyCommand::Statistics),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ProxyCommand::Pause => "PAUSE",
+            ProxyCommand::Resume => "RESUME",
+            ProxyCommand::Terminate => "TERMINATE",
+            ProxyCommand::Statistics => "STATISTICS",
+        }
+    }
+}
+
+impl From<ProxyCommand> for Multipart {
+    fn from(command: ProxyCommand) -> Self {
+        Message::from(command.as_str()).into()
+    }
+}
+
+/// Message counters of one side of a [`Proxy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketStatistics {
+    /// Number of multiparts received by the socket.
+    pub messages_in: u64,
+    /// Number of bytes received by the socket.
+    pub bytes_in: u64,
+    /// Number of multiparts sent by the socket.
+    pub messages_out: u64,
+    /// Number of bytes sent by the socket.
+    pub bytes_out: u64,
+}
+
+/// Statistics of a [`Proxy`], sent as a reply to [`ProxyCommand::Statistics`].
+///
+/// On the wire, the statistics are sent as eight frames containing a `u64` each, in the same
+/// order as `zmq_proxy_steerable`. Like libzmq, which copies the values as they are in memory,
+/// they are encoded in native byte order, so they can only be decoded on a machine with the
+/// same endianness as the proxy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProxyStatistics {
+    /// Counters of the frontend socket.
+    pub frontend: SocketStatistics,
+    /// Counters of the backend socket.
+    pub backend: SocketStatistics,
+}
+
+impl ProxyStatistics {
+    /// Decodes statistics from a reply of the control socket.
+    pub fn from_multipart(multipart: &Multipart) -> Option<Self> {
+        if multipart.len() != 8 {
+            return None;
+        }
+        let mut values = [0u64; 8];
+        for (value, frame) in values.iter_mut().zip(multipart.iter()) {
+            *value = u64::from_ne_bytes((&frame[..]).try_into().ok()?);
+        }
+        Some(Self {
+            frontend: SocketStatistics {
+                messages_in: values[0],
+                bytes_in: values[1],
+                messages_out: values[2],
+                bytes_out: values[3],
+            },
+            backend: SocketStatistics {
+                messages_in: values[4],
+                bytes_in: values[5],
+                messages_out: values[6],
+                bytes_out: values[7],
+            },
+        })
+    }
+}
+
+impl From<ProxyStatistics> for Multipart {
+    fn from(stats: ProxyStatistics) -> Self {
+        [stats.frontend, stats.backend]
+            .iter()
+            .flat_map(|s| [s.messages_in, s.bytes_in, s.messages_out, s.bytes_out])
+            .map(|value| Message::from(&value.to_ne_bytes()[..]))
+            .collect()
+    }
+}
+
+/// Asynchronous proxy between a frontend and a backend socket.
+///
+/// Optionally, every forwarded multipart can be copied to a capture sink and the proxy can be
+/// steered through a control socket (see [`ProxyCommand`]), like `zmq_proxy_steerable`.
+pub struct Proxy<F, B> {
+    frontend: F,
+    backend: B,
+    capture: Option<CaptureSink>,
+    control: Option<ControlSocket>,
+    backend_first: bool,
+}
+
+enum Event {
+    Frontend(Multipart),
+    Backend(Multipart),
+    Control(Multipart),
+    Closed,
+}
+
+impl<F, B> Proxy<F, B>
+where
+    F: ProxySocket + Unpin,
+    B: ProxySocket + Unpin,
+{
+    /// Creates a proxy forwarding messages between `frontend` and `backend`.
+    pub fn new(frontend: F, backend: B) -> Self {
+        Self {
+            frontend,
+            backend,
+            capture: None,
+            control: None,
+            backend_first: false,
+        }
+    }
+
+    /// Sends a copy of every forwarded multipart to `capture`.
+    pub fn capture<C>(mut self, capture: C) -> Self
+    where
+        C: Sink<Multipart, Error = TmqError> + Send + 'static,
+    {
+        self.capture = Some(Box::pin(capture));
+        self
+    }
+
+    /// Receives [`ProxyCommand`]s from `control`, statistics are sent back through it.
+    pub fn control<K>(mut self, control: K) -> Self
+    where
+        K: ProxySocket + Send + 'static,
+    {
+        self.control = Some(Box::pin(control));
+        self
+    }
+
+    /// Runs the proxy until it is terminated or one of its sockets is closed.
+    ///
+    /// The control socket is served while a multipart is being forwarded as well, so a proxy
+    /// whose destination socket can't take any messages can still be terminated.
+    pub async fn run(mut self) -> Result<()> {
+        let mut paused = false;
+        let mut stats = ProxyStatistics::default();
+
+        loop {
+            let (multipart, to_backend) = match poll_fn(|cx| self.poll_event(cx, paused)).await? {
+                Event::Frontend(multipart) => {
+                    record(&mut stats.frontend, &mut stats.backend, &multipart);
+                    (multipart, true)
+                }
+                Event::Backend(multipart) => {
+                    record(&mut stats.backend, &mut stats.frontend, &multipart);
+                    (multipart, false)
+                }
+                Event::Control(multipart) => {
+                    if !handle_command(&mut self.control, &multipart, &mut paused, &stats).await? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Event::Closed => return Ok(()),
+            };
+
+            let Self {
+                frontend,
+                backend,
+                capture,
+                control,
+                ..
+            } = &mut self;
+            let forward = async move {
+                if let Some(capture) = capture.as_mut() {
+                    let copy = multipart.iter().map(|m| Message::from(&m[..])).collect();
+                    capture.send(copy).await?;
+                }
+                if to_backend {
+                    backend.send(multipart).await
+                } else {
+                    frontend.send(multipart).await
+                }
+            };
+            futures::pin_mut!(forward);
+            loop {
+                let command = poll_fn(|cx| match forward.as_mut().poll(cx) {
+                    Poll::Ready(result) => Poll::Ready(result.map(|()| None)),
+                    Poll::Pending => poll_control(control, cx).map(|msg| msg.map(Some)),
+                })
+                .await?;
+                match command {
+                    Some(multipart) => {
+                        if !handle_command(control, &multipart, &mut paused, &stats).await? {
+                            return Ok(());
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    fn poll_event(&mut self, cx: &mut Context<'_>, paused: bool) -> Poll<Result<Event>> {
+        if let Poll::Ready(msg) = poll_control(&mut self.control, cx) {
+            return Poll::Ready(msg.map(Event::Control));
+        }
+
+        if paused {
+            return Poll::Pending;
+        }
+
+        // Alternate between the sides so that a busy frontend can't starve the backend.
+        self.backend_first = !self.backend_first;
+        if self.backend_first {
+            match self.poll_backend(cx) {
+                Poll::Pending => self.poll_frontend(cx),
+                ready => ready,
+            }
+        } else {
+            match self.poll_frontend(cx) {
+                Poll::Pending => self.poll_backend(cx),
+                ready => ready,
+            }
+        }
+    }
+
+    fn poll_frontend(&mut self, cx: &mut Context<'_>) -> Poll<Result<Event>> {
+        Pin::new(&mut self.frontend)
+            .poll_next(cx)
+            .map(|msg| msg.map_or(Ok(Event::Closed), |msg| msg.map(Event::Frontend)))
+    }
+
+    fn poll_backend(&mut self, cx: &mut Context<'_>) -> Poll<Result<Event>> {
+        Pin::new(&mut self.backend)
+            .poll_next(cx)
+            .map(|msg| msg.map_or(Ok(Event::Closed), |msg| msg.map(Event::Backend)))
+    }
+}
+
+/// Receives a command from the control socket, which is dropped once it has been closed.
+fn poll_control(
+    control: &mut Option<ControlSocket>,
+    cx: &mut Context<'_>,
+) -> Poll<Result<Multipart>> {
+    if let Some(socket) = control.as_mut() {
+        match socket.as_mut().poll_next(cx) {
+            Poll::Ready(Some(msg)) => return Poll::Ready(msg),
+            Poll::Ready(None) => *control = None,
+            Poll::Pending => {}
+        }
+    }
+    Poll::Pending
+}
+
+/// Executes a command received from the control socket.
+/// Returns `false` if the proxy has to terminate.
+async fn handle_command(
+    control: &mut Option<ControlSocket>,
+    multipart: &Multipart,
+    paused: &mut bool,
+    stats: &ProxyStatistics,
+) -> Result<bool> {
+    match multipart.0.front().and_then(ProxyCommand::from_message) {
+        Some(ProxyCommand::Pause) => *paused = true,
+        Some(ProxyCommand::Resume) => *paused = false,
+        Some(ProxyCommand::Terminate) => return Ok(false),
+        Some(ProxyCommand::Statistics) => {
+            if let Some(control) = control.as_mut() {
+                control.send((*stats).into()).await?;
+            }
+        }
+        None => log::warn!("Invalid proxy command"),
+    }
+    Ok(true)
+}
+
+fn record(from: &mut SocketStatistics, to: &mut SocketStatistics, multipart: &Multipart) {
+    let bytes = multipart.iter().map(|m| m.len() as u64).sum::<u64>();
+    from.messages_in += 1;
+    from.bytes_in += bytes;
+    to.messages_out += 1;
+    to.bytes_out += bytes;
+}
@@ -1,32 +1,140 @@
-use crate::Result;
+use crate::{context::OwnedSocket, message::Frame, Result};
 
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::{
+    raw::{c_int, c_void},
+    unix::io::{AsRawFd, RawFd},
+};
 
 /// Wrapper on top of a ZMQ socket.
 ///
 /// The socket needs to be wrapped to allow various trait implementations.
 pub(crate) struct SocketWrapper {
-    pub(crate) socket: zmq::Socket,
+    pub(crate) socket: OwnedSocket,
     // This RawFd is held separately because it must be accessible
     // without error after SocketWrapper initialization, for the AsRawFd trait.
     fd: RawFd,
 }
 
 impl SocketWrapper {
-    pub fn new(socket: zmq::Socket) -> Result<Self> {
-        Ok(Self {
-            fd: socket.get_fd()?,
-            socket,
-        })
+    pub fn new(socket: OwnedSocket) -> Result<Self> {
+        let fd = socket.get_fd()?;
+        Ok(Self { socket, fd })
     }
 }
 
+impl SocketWrapper {
+    /// Sends a single frame of a multipart.
+    pub(crate) fn send_frame(&self, frame: &Frame, flags: i32) -> zmq::Result<()> {
+        match frame {
+            Frame::Message(message) => self.socket.send(&**message, flags),
+            Frame::Bytes(bytes) => self.send_bytes(bytes, flags),
+        }
+    }
+
+    /// Sends `bytes` as a frame without copying it.
+    ///
+    /// libzmq holds a clone of `bytes` until it has sent the frame, and releases it through
+    /// `drop_bytes`, possibly from one of its I/O threads.
+    fn send_bytes(&self, bytes: &bytes::Bytes, flags: i32) -> zmq::Result<()> {
+        let mut msg = zmq_sys::zmq_msg_t::default();
+        if bytes.is_empty() {
+            // SAFETY: `msg` is a fresh message.
+            unsafe { zmq_sys::zmq_msg_init(&mut msg) };
+        } else {
+            let hint = Box::into_raw(Box::new(bytes.clone()));
+            // SAFETY: the data lives as long as `hint`, which is dropped by `drop_bytes`.
+            let rc = unsafe {
+                zmq_sys::zmq_msg_init_data(
+                    &mut msg,
+                    bytes.as_ptr() as *mut c_void,
+                    bytes.len(),
+                    Some(drop_bytes),
+                    hint as *mut c_void,
+                )
+            };
+            if rc == -1 {
+                // SAFETY: libzmq didn't take ownership of the hint.
+                drop(unsafe { Box::from_raw(hint) });
+                return Err(last_error());
+            }
+        }
+
+        // SAFETY: the socket is open as long as `self`.
+        if unsafe { zmq_sys::zmq_msg_send(&mut msg, self.socket.as_raw(), flags as c_int) } == -1 {
+            let error = last_error();
+            // The message is still owned by us if it couldn't be sent, closing it releases the
+            // clone of `bytes`.
+            unsafe { zmq_sys::zmq_msg_close(&mut msg) };
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+unsafe extern "C" fn drop_bytes(_data: *mut c_void, hint: *mut c_void) {
+    drop(Box::from_raw(hint as *mut bytes::Bytes));
+}
+
+fn last_error() -> zmq::Error {
+    zmq::Error::from_raw(unsafe { zmq_sys::zmq_errno() })
+}
+
 impl AsRawFd for SocketWrapper {
     fn as_raw_fd(&self) -> RawFd {
         self.fd
     }
 }
 
+/// Sets an integer socket option which isn't exposed by the `zmq` crate.
+pub(crate) fn set_raw_option(socket: &mut zmq::Socket, option: u32, value: i32) -> Result<()> {
+    setsockopt(
+        socket,
+        option,
+        &value as *const i32 as *const c_void,
+        std::mem::size_of::<i32>(),
+    )
+}
+
+/// Reads an integer socket option which isn't exposed by the `zmq` crate.
+pub(crate) fn get_raw_option(socket: &mut zmq::Socket, option: u32) -> Result<i32> {
+    let mut value: i32 = 0;
+    let mut size = std::mem::size_of::<i32>();
+    let rc = unsafe {
+        zmq_sys::zmq_getsockopt(
+            socket.as_mut_ptr(),
+            option as c_int,
+            &mut value as *mut i32 as *mut c_void,
+            &mut size,
+        )
+    };
+    if rc == -1 {
+        return Err(zmq::Error::from_raw(unsafe { zmq_sys::zmq_errno() }).into());
+    }
+    Ok(value)
+}
+
+/// Sets a binary socket option which isn't exposed by the `zmq` crate.
+pub(crate) fn set_raw_bytes_option(
+    socket: &mut zmq::Socket,
+    option: u32,
+    value: &[u8],
+) -> Result<()> {
+    setsockopt(socket, option, value.as_ptr() as *const c_void, value.len())
+}
+
+fn setsockopt(
+    socket: &mut zmq::Socket,
+    option: u32,
+    value: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let rc = unsafe { zmq_sys::zmq_setsockopt(socket.as_mut_ptr(), option as c_int, value, size) };
+    if rc == -1 {
+        return Err(zmq::Error::from_raw(unsafe { zmq_sys::zmq_errno() }).into());
+    }
+    Ok(())
+}
+
 /// Trait for various ZMQ socket wrappers.
 pub trait AsZmqSocket {
     /// Return a reference to the inner ZMQ socket.
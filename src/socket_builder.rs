@@ -1,5 +1,8 @@
-use crate::{FromZmqSocket, TmqError};
-use zmq::{Context, SocketType};
+use crate::{
+    context::{ContextRef, OwnedSocket},
+    AsContext, FromZmqSocket, SocketMonitor, TmqError,
+};
+use zmq::SocketType;
 
 macro_rules! setter {
     ($name: ident, $type: ty, $doc: expr) => {
@@ -31,7 +34,8 @@ macro_rules! setter {
 /// [`bind`]: struct.SocketBuilder.html#method.bind
 /// [`connect`]: struct.SocketBuilder.html#method.connect
 pub struct SocketBuilder<T> {
-    socket: Option<::zmq::Socket>,
+    context: ContextRef,
+    socket: Option<OwnedSocket>,
     error: Option<TmqError>,
     _phantom: std::marker::PhantomData<T>,
 }
@@ -41,7 +45,8 @@ where
     T: FromZmqSocket<T>,
 {
     #[doc(hidden)]
-    pub(crate) fn new(context: &Context, socket_type: SocketType) -> Self {
+    pub(crate) fn new<C: AsContext + ?Sized>(context: &C, socket_type: SocketType) -> Self {
+        let context = ContextRef::from_context(context);
         let mut socket = None;
 
         //Defer the Error to make things easier for consumers
@@ -49,10 +54,11 @@ where
 
         match context.socket(socket_type) {
             Ok(sock) => socket = Some(sock),
-            Err(err) => error = Some(err.into()),
+            Err(err) => error = Some(err),
         };
 
         Self {
+            context,
             socket,
             error,
             _phantom: Default::default(),
@@ -61,26 +67,46 @@ where
 
     /// Connect to a ZMQ endpoint at the given address.
     pub fn connect(self, endpoint: &str) -> crate::Result<T> {
-        if let Some(err) = self.error {
-            return Err(err);
-        }
-
-        let socket = self.socket.unwrap();
-        socket.connect(endpoint)?;
-        T::from_zmq_socket(socket)
+        self.build(endpoint, zmq::Socket::connect)
     }
 
     /// Bind to a ZMQ endpoint at the given address.
     pub fn bind(self, endpoint: &str) -> crate::Result<T> {
-        if let Some(err) = self.error {
+        self.build(endpoint, zmq::Socket::bind)
+    }
+
+    fn build<F>(mut self, endpoint: &str, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(&zmq::Socket, &str) -> zmq::Result<()>,
+    {
+        if let Some(err) = self.error.take() {
             return Err(err);
         }
 
-        let socket = self.socket.unwrap();
-        socket.bind(endpoint)?;
+        let socket = self.socket.take().unwrap();
+        f(&socket, endpoint)?;
+        socket.add_endpoint(endpoint);
         T::from_zmq_socket(socket)
     }
 
+    /// Applies a socket-specific option, deferring any error until `bind`/`connect`.
+    pub(crate) fn apply<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut zmq::Socket) -> crate::Result<()>,
+    {
+        if self.error.is_some() {
+            return self;
+        }
+
+        if let Some(ref mut socket) = self.socket {
+            if let Err(err) = f(socket) {
+                self.error = Some(err);
+            }
+        }
+
+        self
+    }
+
     /// Configure the socket for [monitoring](http://api.zeromq.org/4-2:zmq-socket-monitor)
     pub fn monitor(mut self, monitor_endpoint: &str, events: i32) -> Self {
         if self.error.is_some() {
@@ -96,6 +122,28 @@ where
         self
     }
 
+    /// Configure the socket for monitoring on an automatically generated inproc endpoint.
+    ///
+    /// The returned builder yields a [`SocketMonitor`] stream of the given `events` (e.g.
+    /// `zmq::SocketEvent::ALL.to_raw() as i32`) together with the socket.
+    /// Socket options need to be set before calling this method.
+    pub fn monitored(mut self, events: i32) -> MonitoredSocketBuilder<T> {
+        let mut monitor = None;
+        if self.error.is_none() {
+            if let Some(ref socket) = self.socket {
+                match SocketMonitor::new(&self.context, socket, events) {
+                    Ok(m) => monitor = Some(m),
+                    Err(err) => self.error = Some(err),
+                }
+            }
+        }
+
+        MonitoredSocketBuilder {
+            builder: self,
+            monitor,
+        }
+    }
+
     setter!(set_ipv6, bool, "Setter for the `ZMQ_IPV6` option.");
     setter!(
         set_immediate,
@@ -209,6 +257,41 @@ where
         i32,
         "Setter for the `ZMQ_TCP_KEEPALIVE_INTVL` option."
     );
-    setter!(set_rcvtimeo, i32, "Setter for the `ZMQ_RCVTIMEO` option.");
-    setter!(set_sndtimeo, i32, "Setter for the `ZMQ_SNDTIMEO` option.");
+    setter!(
+        set_rcvtimeo,
+        i32,
+        "Setter for the `ZMQ_RCVTIMEO` option, the default timeout of [`SocketTimeoutExt::recv_timeout`](trait.SocketTimeoutExt.html#method.recv_timeout)."
+    );
+    setter!(
+        set_sndtimeo,
+        i32,
+        "Setter for the `ZMQ_SNDTIMEO` option, the default timeout of [`SocketTimeoutExt::send_timeout`](trait.SocketTimeoutExt.html#method.send_timeout)."
+    );
+}
+
+/// Builder returned by [`SocketBuilder::monitored`] whose [`bind`] and [`connect`] methods return
+/// the socket together with its [`SocketMonitor`].
+///
+/// [`bind`]: struct.MonitoredSocketBuilder.html#method.bind
+/// [`connect`]: struct.MonitoredSocketBuilder.html#method.connect
+pub struct MonitoredSocketBuilder<T> {
+    builder: SocketBuilder<T>,
+    monitor: Option<SocketMonitor>,
+}
+
+impl<T> MonitoredSocketBuilder<T>
+where
+    T: FromZmqSocket<T>,
+{
+    /// Connect to a ZMQ endpoint at the given address.
+    pub fn connect(self, endpoint: &str) -> crate::Result<(T, SocketMonitor)> {
+        let socket = self.builder.connect(endpoint)?;
+        Ok((socket, self.monitor.unwrap()))
+    }
+
+    /// Bind to a ZMQ endpoint at the given address.
+    pub fn bind(self, endpoint: &str) -> crate::Result<(T, SocketMonitor)> {
+        let socket = self.builder.bind(endpoint)?;
+        Ok((socket, self.monitor.unwrap()))
+    }
 }
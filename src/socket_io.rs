@@ -0,0 +1,216 @@
+use std::{
+    io, mem,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{socket::AsZmqSocket, Message, Multipart, Result, TmqError};
+
+const DEFAULT_FRAME_SIZE: usize = 64 * 1024;
+
+/// Byte stream adapter, which makes a socket usable as `AsyncRead` and/or `AsyncWrite`.
+///
+/// Written bytes are collected and sent in single-frame messages of up to
+/// [`frame_size`](#method.frame_size) bytes, a message is sent as soon as it is full or the
+/// adapter is flushed. Shutting down the adapter sends a message with a single zero-length frame,
+/// which marks the end of the stream.
+///
+/// All frames of the received messages are joined into one contiguous byte stream, until a message
+/// consisting of a single zero-length frame signals EOF.
+///
+/// If the inner socket is a `Sink` of multiparts, `SocketIo` is `AsyncWrite`. If the inner socket
+/// is a `Stream` of multiparts, `SocketIo` is `AsyncRead`. The adapters of a PUSH and a PULL socket
+/// can be combined with `tokio::io::join`.
+///
+/// ## Usage Example
+/// ```rust,no_run
+/// use tmq::{pair, Context, Result};
+/// use tokio::io::AsyncWriteExt;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let mut io = pair(&Context::new())
+///         .connect("tcp://127.0.0.1:7899")?
+///         .into_io()
+///         .with_frame_size(8192);
+///
+///     let mut file = tokio::fs::File::open("archive.tar").await?;
+///     tokio::io::copy(&mut file, &mut io).await?;
+///     io.shutdown().await?;
+///     Ok(())
+/// }
+/// ```
+pub struct SocketIo<S> {
+    socket: S,
+    frame_size: usize,
+    // Bytes which haven't been sent yet.
+    write_buffer: Vec<u8>,
+    eof_sent: bool,
+    // Frames which haven't been read yet, and the read offset into the first one.
+    read_buffer: Multipart,
+    read_offset: usize,
+    eof_received: bool,
+}
+
+impl<S> SocketIo<S> {
+    /// Wraps `socket`, sending frames of at most 64 KiB.
+    pub fn new(socket: S) -> Self {
+        Self {
+            socket,
+            frame_size: DEFAULT_FRAME_SIZE,
+            write_buffer: Vec::new(),
+            eof_sent: false,
+            read_buffer: Multipart::default(),
+            read_offset: 0,
+            eof_received: false,
+        }
+    }
+
+    /// Sets the maximum size of the sent frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_size` is zero.
+    pub fn with_frame_size(mut self, frame_size: usize) -> Self {
+        assert!(frame_size > 0, "frame size must be positive");
+        self.frame_size = frame_size;
+        self
+    }
+
+    /// Returns the maximum size of the sent frames.
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Returns a reference to the inner socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns a mutable reference to the inner socket.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.socket
+    }
+
+    /// Returns the inner socket.
+    ///
+    /// Bytes which have been written but not flushed yet, and bytes which have been received but
+    /// not read yet, are lost.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+}
+
+impl<S: AsZmqSocket> AsZmqSocket for SocketIo<S> {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.socket.get_socket()
+    }
+}
+
+fn io_error(error: TmqError) -> io::Error {
+    match error {
+        TmqError::Io(error) => error,
+        error => io::Error::other(error),
+    }
+}
+
+impl<S> SocketIo<S>
+where
+    S: Sink<Multipart, Error = TmqError> + Unpin,
+{
+    /// Sends the buffered bytes as a single frame, an empty buffer is sent as the EOF frame.
+    fn poll_send_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut self.socket).poll_ready(cx)).map_err(io_error)?;
+        let frame = Message::from(mem::take(&mut self.write_buffer));
+        Pin::new(&mut self.socket)
+            .start_send(Multipart::from(vec![frame]))
+            .map_err(io_error)?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for SocketIo<S>
+where
+    S: Sink<Multipart, Error = TmqError> + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.eof_sent {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if self.write_buffer.len() >= self.frame_size {
+            ready!(self.poll_send_frame(cx))?;
+        }
+        let len = buf.len().min(self.frame_size - self.write_buffer.len());
+        self.write_buffer.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.write_buffer.is_empty() {
+            ready!(self.poll_send_frame(cx))?;
+        }
+        Pin::new(&mut self.socket).poll_flush(cx).map_err(io_error)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.write_buffer.is_empty() {
+            ready!(self.poll_send_frame(cx))?;
+        }
+        if !self.eof_sent {
+            ready!(self.poll_send_frame(cx))?;
+            self.eof_sent = true;
+        }
+        Pin::new(&mut self.socket).poll_flush(cx).map_err(io_error)
+    }
+}
+
+impl<S> AsyncRead for SocketIo<S>
+where
+    S: Stream<Item = Result<Multipart>> + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if let Some(frame) = this.read_buffer.0.front() {
+                let remaining = &frame[this.read_offset..];
+                let len = remaining.len().min(buf.remaining());
+                buf.put_slice(&remaining[..len]);
+                this.read_offset += len;
+                if len == remaining.len() {
+                    this.read_buffer.pop_front();
+                    this.read_offset = 0;
+                }
+                if len > 0 || buf.remaining() == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+            if this.eof_received {
+                return Poll::Ready(Ok(()));
+            }
+
+            match ready!(Pin::new(&mut this.socket).poll_next(cx)) {
+                Some(Ok(multipart)) => {
+                    if multipart.len() == 1 && multipart[0].is_empty() {
+                        this.eof_received = true;
+                    } else {
+                        this.read_buffer = multipart;
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Err(io_error(e))),
+                None => this.eof_received = true,
+            }
+        }
+    }
+}
@@ -1,9 +1,15 @@
-use zmq::Context as ZmqContext;
+use std::{collections::HashMap, task::Poll, time::Duration};
 
-use crate::{comm::SenderReceiver, poll::ZmqPoller, FromZmqSocket, SocketBuilder};
+use futures::{future::poll_fn, SinkExt, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    comm::SenderReceiver, context::OwnedSocket, poll::ZmqPoller, AsContext, FromZmqSocket, Message,
+    Multipart, Result, SocketBuilder, TmqError,
+};
 
 /// Create a builder for a DEALER socket.
-pub fn dealer(context: &ZmqContext) -> SocketBuilder<Dealer> {
+pub fn dealer(context: &impl AsContext) -> SocketBuilder<Dealer> {
     SocketBuilder::new(context, zmq::SocketType::DEALER)
 }
 
@@ -13,7 +19,7 @@ pub struct Dealer {
 }
 
 impl FromZmqSocket<Dealer> for Dealer {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -23,3 +29,155 @@ impl FromZmqSocket<Dealer> for Dealer {
 impl_wrapper!(Dealer, SenderReceiver, inner);
 impl_wrapper_sink!(Dealer, inner);
 impl_wrapper_stream!(Dealer, inner);
+impl_buffered!(Dealer, inner);
+impl_split!(Dealer, inner);
+impl_into_io!(Dealer);
+
+impl Dealer {
+    /// Spawns a task driving this socket and returns an [`AsyncRequester`] handle to it.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn into_requester(self) -> AsyncRequester {
+        let (commands, receiver) = mpsc::channel(REQUESTER_QUEUE_SIZE);
+        tokio::spawn(drive_requester(self, receiver));
+        AsyncRequester { commands }
+    }
+}
+
+const REQUESTER_QUEUE_SIZE: usize = 128;
+const MIN_SWEEP_THRESHOLD: usize = 64;
+
+type PendingReply = oneshot::Sender<Result<Multipart>>;
+
+/// Cloneable handle for sending many concurrent requests over a single DEALER socket.
+///
+/// Each request is sent as `[correlation id, empty delimiter, body...]`, the same envelope a REQ
+/// socket with `ZMQ_REQ_CORRELATE` uses, so REP sockets and the
+/// [`ReplyServer`](../request_reply/struct.ReplyServer.html) return it unchanged.
+/// Replies are matched to their requests by the correlation id, in any order.
+///
+/// ## Usage Example
+///
+/// ```rust,no_run
+/// use tmq::{dealer, Context, Result};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let requester = dealer(&Context::new())
+///         .connect("tcp://127.0.0.1:7897")?
+///         .into_requester();
+///
+///     let tasks = (0..10).map(|i| {
+///         let requester = requester.clone();
+///         tokio::spawn(async move { requester.request(vec![&i.to_string()].into()).await })
+///     });
+///     for reply in futures::future::join_all(tasks).await {
+///         println!("Reply: {:?}", reply.unwrap()?);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AsyncRequester {
+    commands: mpsc::Sender<(Multipart, PendingReply)>,
+}
+
+impl AsyncRequester {
+    /// Sends a request and waits for its reply.
+    ///
+    /// Returns [`TmqError::Closed`] if the socket has failed.
+    pub async fn request(&self, msg: Multipart) -> Result<Multipart> {
+        let (sender, receiver) = oneshot::channel();
+        self.commands
+            .send((msg, sender))
+            .await
+            .map_err(|_| TmqError::Closed)?;
+        receiver.await.map_err(|_| TmqError::Closed)?
+    }
+
+    /// Sends a request and waits for its reply for at most `timeout`.
+    ///
+    /// Returns [`TmqError::Timeout`] if the reply doesn't arrive in time. A reply which arrives
+    /// later is discarded.
+    pub async fn request_timeout(&self, msg: Multipart, timeout: Duration) -> Result<Multipart> {
+        tokio::time::timeout(timeout, self.request(msg))
+            .await
+            .unwrap_or(Err(TmqError::Timeout))
+    }
+}
+
+enum RequesterEvent {
+    Request(Multipart, PendingReply),
+    Reply(Result<Multipart>),
+    Closed,
+}
+
+async fn drive_requester(
+    mut socket: Dealer,
+    mut commands: mpsc::Receiver<(Multipart, PendingReply)>,
+) {
+    let mut pending: HashMap<u64, PendingReply> = HashMap::new();
+    let mut next_id: u64 = 0;
+    let mut sweep_threshold = MIN_SWEEP_THRESHOLD;
+
+    loop {
+        let event = poll_fn(|cx| {
+            match socket.poll_next_unpin(cx) {
+                Poll::Ready(Some(reply)) => return Poll::Ready(RequesterEvent::Reply(reply)),
+                Poll::Ready(None) => return Poll::Ready(RequesterEvent::Closed),
+                Poll::Pending => {}
+            }
+            commands.poll_recv(cx).map(|command| match command {
+                Some((msg, reply)) => RequesterEvent::Request(msg, reply),
+                None => RequesterEvent::Closed,
+            })
+        })
+        .await;
+
+        match event {
+            RequesterEvent::Request(mut msg, reply) => {
+                if reply.is_closed() {
+                    continue;
+                }
+                next_id = next_id.wrapping_add(1);
+                msg.push_front(Message::new());
+                msg.push_front(Message::from(&next_id.to_be_bytes()[..]));
+                if let Err(e) = socket.send(msg).await {
+                    let _ = reply.send(Err(e));
+                    continue;
+                }
+                pending.insert(next_id, reply);
+
+                // Forget requests whose callers have given up (e.g. timed out).
+                if pending.len() >= sweep_threshold {
+                    pending.retain(|_, reply| !reply.is_closed());
+                    sweep_threshold = (pending.len() * 2).max(MIN_SWEEP_THRESHOLD);
+                }
+            }
+            RequesterEvent::Reply(Ok(mut msg)) => match split_correlation_id(&mut msg) {
+                Some(id) => match pending.remove(&id) {
+                    Some(reply) => {
+                        let _ = reply.send(Ok(msg));
+                    }
+                    None => log::debug!("Discarding orphaned reply {}", id),
+                },
+                None => log::warn!("Discarding reply without a correlation id"),
+            },
+            RequesterEvent::Reply(Err(e)) => {
+                log::error!("Requester socket failed: {}", e);
+                break;
+            }
+            RequesterEvent::Closed => break,
+        }
+    }
+}
+
+/// Removes the `[correlation id, empty delimiter]` envelope from a reply.
+fn split_correlation_id(reply: &mut Multipart) -> Option<u64> {
+    if reply.len() < 2 || !reply[1].is_empty() {
+        return None;
+    }
+    let id = u64::from_be_bytes((&reply[0][..]).try_into().ok()?);
+    reply.0.drain(..2);
+    Some(id)
+}
@@ -12,8 +12,14 @@ pub mod push;
 pub mod request_reply;
 /// Router Sockets
 pub mod router;
+/// Stream Sockets
+pub mod stream;
 /// Subscribe Sockets
 pub mod subscribe;
+/// XPublish Sockets
+pub mod xpublish;
+/// XSubscribe Sockets
+pub mod xsubscribe;
 
 pub use dealer::dealer;
 pub use pair::pair;
@@ -21,11 +27,16 @@ pub use publish::publish;
 pub use pull::pull;
 pub use push::push;
 pub use request_reply::reply;
+pub use request_reply::reply_server;
 pub use request_reply::request;
+pub use request_reply::request_client;
 pub use router::router;
+pub use stream::stream;
 pub use subscribe::subscribe;
+pub use xpublish::xpublish;
+pub use xsubscribe::xsubscribe;
 
 #[doc(hidden)]
 pub trait FromZmqSocket<T> {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<T>;
+    fn from_zmq_socket(socket: crate::context::OwnedSocket) -> crate::Result<T>;
 }
@@ -1,9 +1,10 @@
-use zmq::Context as ZmqContext;
-
-use crate::{comm::SenderReceiver, poll::ZmqPoller, FromZmqSocket, SocketBuilder};
+use crate::{
+    comm::SenderReceiver, context::OwnedSocket, poll::ZmqPoller, AsContext, FromZmqSocket,
+    SocketBuilder,
+};
 
 /// Create a builder for a PAIR socket.
-pub fn pair(context: &ZmqContext) -> SocketBuilder<Pair> {
+pub fn pair(context: &impl AsContext) -> SocketBuilder<Pair> {
     SocketBuilder::new(context, zmq::SocketType::PAIR)
 }
 
@@ -13,7 +14,7 @@ pub struct Pair {
 }
 
 impl FromZmqSocket<Pair> for Pair {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -23,3 +24,6 @@ impl FromZmqSocket<Pair> for Pair {
 impl_wrapper!(Pair, SenderReceiver, inner);
 impl_wrapper_sink!(Pair, inner);
 impl_wrapper_stream!(Pair, inner);
+impl_buffered!(Pair, inner);
+impl_split!(Pair, inner);
+impl_into_io!(Pair);
@@ -1,6 +1,6 @@
-use zmq::Context as ZmqContext;
-
-use crate::{poll::ZmqPoller, FromZmqSocket, Sender, SocketBuilder};
+use crate::{
+    context::OwnedSocket, poll::ZmqPoller, AsContext, FromZmqSocket, Sender, SocketBuilder,
+};
 
 /// Create a builder for a PUB socket.
 ///
@@ -33,7 +33,7 @@ use crate::{poll::ZmqPoller, FromZmqSocket, Sender, SocketBuilder};
 ///     }
 /// }
 /// ```
-pub fn publish(context: &ZmqContext) -> SocketBuilder<Publish> {
+pub fn publish(context: &impl AsContext) -> SocketBuilder<Publish> {
     SocketBuilder::new(context, zmq::SocketType::PUB)
 }
 
@@ -43,7 +43,7 @@ pub struct Publish {
 }
 
 impl FromZmqSocket<Publish> for Publish {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: Sender::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -52,3 +52,14 @@ impl FromZmqSocket<Publish> for Publish {
 
 impl_wrapper!(Publish, Sender, inner);
 impl_wrapper_sink!(Publish, inner);
+impl_buffered_sender!(Publish, inner);
+
+impl Publish {
+    /// Spawns a task owning the socket and returns a cloneable
+    /// [`SenderHandle`](../struct.SenderHandle.html) which can be used to send from many tasks.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn into_handle(self) -> crate::SenderHandle {
+        self.inner.into_handle()
+    }
+}
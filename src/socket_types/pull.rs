@@ -1,9 +1,9 @@
-use zmq::Context as ZmqContext;
-
-use crate::{comm::Receiver, poll::ZmqPoller, FromZmqSocket, SocketBuilder};
+use crate::{
+    comm::Receiver, context::OwnedSocket, poll::ZmqPoller, AsContext, FromZmqSocket, SocketBuilder,
+};
 
 /// Create a builder for a PULL socket.
-pub fn pull(context: &ZmqContext) -> SocketBuilder<Pull> {
+pub fn pull(context: &impl AsContext) -> SocketBuilder<Pull> {
     SocketBuilder::new(context, zmq::SocketType::PULL)
 }
 
@@ -13,7 +13,7 @@ pub struct Pull {
 }
 
 impl FromZmqSocket<Pull> for Pull {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: Receiver::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -22,4 +22,5 @@ impl FromZmqSocket<Pull> for Pull {
 
 impl_wrapper!(Pull, Receiver, inner);
 impl_wrapper_stream!(Pull, inner);
-impl_buffered!(Pull, inner);
+impl_buffered!(Pull, inner, crate::BufferedReceiver);
+impl_into_io!(Pull);
@@ -1,9 +1,9 @@
-use zmq::Context as ZmqContext;
-
-use crate::{comm::Sender, poll::ZmqPoller, FromZmqSocket, SocketBuilder};
+use crate::{
+    comm::Sender, context::OwnedSocket, poll::ZmqPoller, AsContext, FromZmqSocket, SocketBuilder,
+};
 
 /// Create a builder for a PUSH socket.
-pub fn push(context: &ZmqContext) -> SocketBuilder<Push> {
+pub fn push(context: &impl AsContext) -> SocketBuilder<Push> {
     SocketBuilder::new(context, zmq::SocketType::PUSH)
 }
 
@@ -13,7 +13,7 @@ pub struct Push {
 }
 
 impl FromZmqSocket<Push> for Push {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: Sender::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -22,3 +22,15 @@ impl FromZmqSocket<Push> for Push {
 
 impl_wrapper!(Push, Sender, inner);
 impl_wrapper_sink!(Push, inner);
+impl_buffered_sender!(Push, inner);
+impl_into_io!(Push);
+
+impl Push {
+    /// Spawns a task owning the socket and returns a cloneable
+    /// [`SenderHandle`](../struct.SenderHandle.html) which can be used to send from many tasks.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn into_handle(self) -> crate::SenderHandle {
+        self.inner.into_handle()
+    }
+}
@@ -1,20 +1,39 @@
-use std::pin::Pin;
+use std::{collections::VecDeque, future::Future, pin::Pin, task::Poll, time::Duration};
 
-use crate::{poll::ZmqPoller, FromZmqSocket, Multipart, SocketBuilder};
-use zmq::Context as ZmqContext;
+use crate::{
+    comm::SenderReceiver,
+    context::OwnedSocket,
+    message::{split_envelope, Frames},
+    poll::ZmqPoller,
+    AsContext, FromZmqSocket, Message, Multipart, SocketBuilder, TmqError,
+};
+use futures::{future::poll_fn, stream::FuturesUnordered, Sink, StreamExt};
+use tokio::time::Instant;
 
 /// Create a builder for a REQ socket
-pub fn request(context: &ZmqContext) -> SocketBuilder<RequestSender> {
+pub fn request(context: &impl AsContext) -> SocketBuilder<RequestSender> {
     SocketBuilder::new(context, zmq::SocketType::REQ)
 }
 
+impl SocketBuilder<RequestSender> {
+    /// Setter for the `ZMQ_REQ_RELAXED` option.
+    pub fn set_req_relaxed(self, value: bool) -> Self {
+        self.apply(|socket| Ok(socket.set_req_relaxed(value)?))
+    }
+
+    /// Setter for the `ZMQ_REQ_CORRELATE` option.
+    pub fn set_req_correlate(self, value: bool) -> Self {
+        self.apply(|socket| Ok(socket.set_req_correlate(value)?))
+    }
+}
+
 /// A REQ Socket returned from the `request` fn
 pub struct RequestSender {
     inner: ZmqPoller,
 }
 
 impl FromZmqSocket<RequestSender> for RequestSender {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: ZmqPoller::from_zmq_socket(socket)?,
         })
@@ -25,7 +44,8 @@ impl_as_socket!(RequestSender, inner);
 
 impl RequestSender {
     /// Send a multipart message and return a `RequestReceiver`
-    pub async fn send(mut self, mut msg: Multipart) -> crate::Result<RequestReceiver> {
+    pub async fn send(mut self, msg: Multipart) -> crate::Result<RequestReceiver> {
+        let mut msg = msg.into();
         futures::future::poll_fn(|cx| Pin::new(&mut self.inner).multipart_flush(cx, &mut msg))
             .await?;
         Ok(RequestReceiver { inner: self.inner })
@@ -33,7 +53,7 @@ impl RequestSender {
 }
 
 /// Create a builder for a REP socket
-pub fn reply(context: &ZmqContext) -> SocketBuilder<RequestReceiver> {
+pub fn reply(context: &impl AsContext) -> SocketBuilder<RequestReceiver> {
     SocketBuilder::new(context, zmq::SocketType::REP)
 }
 /// A `RequestReceiver` is returned After sending a message
@@ -42,7 +62,7 @@ pub struct RequestReceiver {
 }
 
 impl FromZmqSocket<RequestReceiver> for RequestReceiver {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: ZmqPoller::from_zmq_socket(socket)?,
         })
@@ -59,3 +79,272 @@ impl RequestReceiver {
         Ok((msg, RequestSender { inner: self.inner }))
     }
 }
+
+/// Create a builder for a REQ socket driven by a [`RequestClient`]
+pub fn request_client(context: &impl AsContext) -> SocketBuilder<RequestClient> {
+    SocketBuilder::new(context, zmq::SocketType::REQ)
+}
+
+/// A reusable REQ client returned from the `request_client` fn
+///
+/// Unlike [`RequestSender`], the client is borrowed by each request, so dropping a request
+/// future (e.g. on a timeout) doesn't lose the socket.
+/// The socket always has `ZMQ_REQ_RELAXED` and `ZMQ_REQ_CORRELATE` enabled, which lets a new
+/// request be sent while the reply of an abandoned one is still pending. That stale reply is
+/// then discarded by ZMQ.
+pub struct RequestClient {
+    inner: ZmqPoller,
+    buffer: Frames,
+    buffer_len: usize,
+}
+
+impl FromZmqSocket<RequestClient> for RequestClient {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
+        socket.set_req_relaxed(true)?;
+        socket.set_req_correlate(true)?;
+        Ok(Self {
+            inner: ZmqPoller::from_zmq_socket(socket)?,
+            buffer: Frames::default(),
+            buffer_len: 0,
+        })
+    }
+}
+
+impl_as_socket!(RequestClient, inner);
+
+impl RequestClient {
+    /// Send a multipart message and wait for its reply
+    ///
+    /// This method is cancel-safe: if the returned future is dropped, the client can still be
+    /// used for further requests.
+    pub async fn request(&mut self, msg: Multipart) -> crate::Result<Multipart> {
+        self.recover().await?;
+
+        self.buffer_len = msg.len();
+        self.buffer = msg.into();
+        self.flush().await?;
+
+        let inner = &mut self.inner;
+        poll_fn(move |cx| inner.multipart_recv(cx)).await
+    }
+
+    /// Send a multipart message and wait for its reply for at most `timeout`
+    ///
+    /// Returns [`TmqError::Timeout`] if the reply doesn't arrive in time, the client can be
+    /// reused right away.
+    pub async fn request_timeout(
+        &mut self,
+        msg: Multipart,
+        timeout: Duration,
+    ) -> crate::Result<Multipart> {
+        tokio::time::timeout(timeout, self.request(msg))
+            .await
+            .unwrap_or(Err(TmqError::Timeout))
+    }
+
+    /// Send a multipart message and wait for its reply until `deadline`
+    ///
+    /// Like [`request_timeout`](#method.request_timeout), but a deadline can be shared by
+    /// several requests. It can be given as a `std::time::Instant` or a `tokio::time::Instant`.
+    pub async fn request_deadline<I: Into<Instant>>(
+        &mut self,
+        msg: Multipart,
+        deadline: I,
+    ) -> crate::Result<Multipart> {
+        tokio::time::timeout_at(deadline.into(), self.request(msg))
+            .await
+            .unwrap_or(Err(TmqError::Timeout))
+    }
+
+    /// Finishes a multipart which was only partially sent by an abandoned request.
+    async fn recover(&mut self) -> crate::Result<()> {
+        if self.buffer.len() < self.buffer_len {
+            self.flush().await?;
+        }
+        // Nothing of the previous request has been sent if its frames are all still buffered.
+        self.buffer = Frames::default();
+        self.buffer_len = 0;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> crate::Result<()> {
+        let Self { inner, buffer, .. } = self;
+        let result = poll_fn(move |cx| inner.multipart_flush(cx, buffer)).await;
+        // The message is either sent, or the rest of it was dropped by the failed send.
+        self.buffer = Frames::default();
+        self.buffer_len = 0;
+        result
+    }
+}
+
+/// Create a builder for a [`ReplyServer`]
+///
+/// ## Usage Example
+///
+/// ```rust,no_run
+/// use tmq::{reply_server, Context, Result};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     reply_server(&Context::new())
+///         .bind("tcp://127.0.0.1:7897")?
+///         .concurrency(16)
+///         .serve(|request| async move { Ok(request) })
+///         .await
+/// }
+/// ```
+pub fn reply_server(context: &impl AsContext) -> SocketBuilder<ReplyServer> {
+    SocketBuilder::new(context, zmq::SocketType::ROUTER)
+}
+
+/// A reply server returned from the `reply_server` fn
+///
+/// Requests are passed to an asynchronous handler and several of them can be handled at once.
+/// The server uses a ROUTER socket, so each reply is routed back through the envelope of its
+/// request, which keeps it compatible with REQ and DEALER clients.
+///
+/// Like in REP sockets, the envelope of a request extends to its first empty delimiter by default,
+/// so bodies are cut at their first empty frame. DEALER clients which send bodies with empty
+/// frames should be served with [`delimited_envelopes`](ReplyServer::delimited_envelopes)
+/// disabled.
+pub struct ReplyServer {
+    inner: SenderReceiver,
+    concurrency: usize,
+    delimited_envelopes: bool,
+    error_reply: ErrorReply,
+}
+
+type ErrorReply = Box<dyn Fn(&TmqError) -> Multipart + Send + Sync>;
+
+impl FromZmqSocket<ReplyServer> for ReplyServer {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
+        Ok(Self {
+            inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
+            concurrency: 1,
+            delimited_envelopes: true,
+            error_reply: Box::new(|e| Message::from(e.to_string().as_str()).into()),
+        })
+    }
+}
+
+impl_as_socket!(ReplyServer, inner);
+
+enum ServerEvent {
+    Request(Multipart),
+    Reply(Multipart, crate::Result<Multipart>),
+    Failed(TmqError),
+    Closed,
+}
+
+impl ReplyServer {
+    /// Sets the number of requests which can be handled at once (1 by default)
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        assert!(limit > 0);
+        self.concurrency = limit;
+        self
+    }
+
+    /// Treats all frames up to the first empty delimiter as the envelope of a request (`true` by
+    /// default)
+    ///
+    /// This is how REP sockets split requests. It is needed for REQ clients with
+    /// `ZMQ_REQ_CORRELATE`, [`AsyncRequester`](crate::dealer::AsyncRequester)s and clients behind
+    /// ROUTER/DEALER proxies, which put more routing frames in front of the delimiter.
+    ///
+    /// When disabled, the envelope is only the identity of the client, followed by an empty
+    /// delimiter if one comes directly after it. Requests of DEALER clients then keep all of their
+    /// frames, including empty ones.
+    pub fn delimited_envelopes(mut self, value: bool) -> Self {
+        self.delimited_envelopes = value;
+        self
+    }
+
+    /// Sets the reply which is sent when the handler returns an error
+    ///
+    /// Clients wait for a reply to every request, so one is sent for failed requests as well.
+    /// By default, it is a single frame with the error message.
+    pub fn error_reply<F>(mut self, reply: F) -> Self
+    where
+        F: Fn(&TmqError) -> Multipart + Send + Sync + 'static,
+    {
+        self.error_reply = Box::new(reply);
+        self
+    }
+
+    /// Handle incoming requests with `handler` until the socket fails
+    ///
+    /// If the handler returns an error, it is logged and the
+    /// [`error_reply`](ReplyServer::error_reply) is sent for that request.
+    ///
+    /// New requests keep being received while replies wait to be sent, e.g. to a client which
+    /// doesn't read them. Waiting replies count towards the
+    /// [`concurrency`](ReplyServer::concurrency) limit.
+    pub async fn serve<F, Fut>(mut self, handler: F) -> crate::Result<()>
+    where
+        F: Fn(Multipart) -> Fut,
+        Fut: Future<Output = crate::Result<Multipart>>,
+    {
+        let handler = &handler;
+        let mut in_flight = FuturesUnordered::new();
+        // Replies which haven't been passed to the socket yet, they count towards the
+        // concurrency limit so that a slow client can't make them pile up.
+        let mut replies = VecDeque::new();
+
+        loop {
+            let event = poll_fn(|cx| {
+                // Replies are sent while new requests are received, so a reply which can't be
+                // sent right away doesn't hold up the other requests.
+                while !replies.is_empty() {
+                    match Sink::<Multipart>::poll_ready(Pin::new(&mut self.inner), cx) {
+                        Poll::Ready(Ok(())) => {
+                            let reply = replies.pop_front().unwrap();
+                            if let Err(e) = Pin::new(&mut self.inner).start_send(reply) {
+                                return Poll::Ready(ServerEvent::Failed(e));
+                            }
+                        }
+                        Poll::Ready(Err(e)) => return Poll::Ready(ServerEvent::Failed(e)),
+                        Poll::Pending => break,
+                    }
+                }
+                if let Poll::Ready(Err(e)) =
+                    Sink::<Multipart>::poll_flush(Pin::new(&mut self.inner), cx)
+                {
+                    return Poll::Ready(ServerEvent::Failed(e));
+                }
+
+                if let Poll::Ready(Some((envelope, reply))) = in_flight.poll_next_unpin(cx) {
+                    return Poll::Ready(ServerEvent::Reply(envelope, reply));
+                }
+                if in_flight.len() + replies.len() < self.concurrency {
+                    match self.inner.poll_next_unpin(cx) {
+                        Poll::Ready(Some(Ok(request))) => {
+                            return Poll::Ready(ServerEvent::Request(request))
+                        }
+                        Poll::Ready(Some(Err(e))) => return Poll::Ready(ServerEvent::Failed(e)),
+                        Poll::Ready(None) => return Poll::Ready(ServerEvent::Closed),
+                        Poll::Pending => {}
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match event {
+                ServerEvent::Request(mut request) => {
+                    let envelope = split_envelope(&mut request, self.delimited_envelopes);
+                    in_flight.push(async move { (envelope, handler(request).await) });
+                }
+                ServerEvent::Reply(mut envelope, reply) => {
+                    let reply = reply.unwrap_or_else(|e| {
+                        log::error!("Reply handler failed: {}", e);
+                        (self.error_reply)(&e)
+                    });
+                    envelope.0.extend(reply);
+                    replies.push_back(envelope);
+                }
+                ServerEvent::Failed(e) => return Err(e),
+                ServerEvent::Closed => return Ok(()),
+            }
+        }
+    }
+}
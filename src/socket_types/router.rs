@@ -1,22 +1,63 @@
-use zmq::Context as ZmqContext;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Sink, Stream};
 
 use crate::{
-    comm::SenderReceiver, poll::ZmqPoller, socket::AsZmqSocket, FromZmqSocket, Result,
-    SocketBuilder,
+    comm::SenderReceiver,
+    context::OwnedSocket,
+    poll::ZmqPoller,
+    socket::{get_raw_option, set_raw_option, AsZmqSocket},
+    AsContext, FromZmqSocket, Message, Multipart, Result, SinkItem, SocketBuilder, TmqError,
 };
 
 /// Create a builder for a ROUTER socket.
-pub fn router(context: &ZmqContext) -> SocketBuilder<Router> {
+pub fn router(context: &impl AsContext) -> SocketBuilder<Router> {
     SocketBuilder::new(context, zmq::SocketType::ROUTER)
 }
 
+impl SocketBuilder<Router> {
+    /// Setter for the `ZMQ_ROUTER_MANDATORY` option.
+    pub fn set_router_mandatory(self, value: bool) -> Self {
+        self.apply(|socket| Ok(socket.set_router_mandatory(value)?))
+    }
+
+    /// Setter for the `ZMQ_ROUTER_HANDOVER` option.
+    pub fn set_router_handover(self, value: bool) -> Self {
+        self.apply(|socket| Ok(socket.set_router_handover(value)?))
+    }
+
+    /// Setter for the `ZMQ_ROUTER_NOTIFY` option, a combination of [`NOTIFY_CONNECT`] and
+    /// [`NOTIFY_DISCONNECT`].
+    ///
+    /// This option is part of the libzmq DRAFT API, building the socket fails with `EINVAL` if
+    /// libzmq was built without it.
+    /// See [`PeerTracker`] for handling the notifications.
+    pub fn set_router_notify(self, value: i32) -> Self {
+        self.apply(|socket| set_raw_option(socket, ZMQ_ROUTER_NOTIFY, value))
+    }
+}
+
+/// Value of the `ZMQ_ROUTER_NOTIFY` option from the libzmq DRAFT API, which `zmq_sys` doesn't
+/// define.
+const ZMQ_ROUTER_NOTIFY: u32 = 97;
+
+/// `ZMQ_ROUTER_NOTIFY` flag which enables notifications about connected peers.
+pub const NOTIFY_CONNECT: i32 = 1;
+/// `ZMQ_ROUTER_NOTIFY` flag which enables notifications about disconnected peers.
+pub const NOTIFY_DISCONNECT: i32 = 2;
+
 /// Asynchronous ROUTER socket.
 pub struct Router {
     inner: SenderReceiver,
 }
 
 impl FromZmqSocket<Router> for Router {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self {
             inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
         })
@@ -26,6 +67,8 @@ impl FromZmqSocket<Router> for Router {
 impl_wrapper!(Router, SenderReceiver, inner);
 impl_wrapper_sink!(Router, inner);
 impl_wrapper_stream!(Router, inner);
+impl_buffered!(Router, inner);
+impl_split!(Router, inner);
 
 impl Router {
     /// Accessor for the `ZMQ_ROUTER_MANDATORY` option.
@@ -49,4 +92,372 @@ impl Router {
         self.get_socket().set_router_handover(value)?;
         Ok(())
     }
+
+    /// Wraps the socket in a [`PeerTracker`] which turns `ZMQ_ROUTER_NOTIFY` notifications into
+    /// [`PeerEvent`]s.
+    ///
+    /// Fails with `EINVAL` if libzmq was built without the DRAFT API or [`NOTIFY_DISCONNECT`] isn't
+    /// enabled, as disconnected peers could never be removed from the tracker without it.
+    pub fn track_peers(mut self) -> Result<PeerTracker> {
+        let notify = get_raw_option(self.inner.poller.get_socket_mut(), ZMQ_ROUTER_NOTIFY)?;
+        if notify & NOTIFY_DISCONNECT == 0 {
+            return Err(zmq::Error::EINVAL.into());
+        }
+        Ok(PeerTracker {
+            router: self,
+            notify,
+            peers: HashSet::new(),
+            pending: None,
+        })
+    }
+
+    /// Wraps the socket in a [`Routed`] adapter which separates the peer identity from the
+    /// message.
+    pub fn routed(self) -> Routed {
+        Routed {
+            router: self,
+            delimited_envelopes: false,
+            envelopes: HashMap::new(),
+            destination: None,
+        }
+    }
+}
+
+/// Routing identity of a peer connected to a ROUTER socket.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(Vec<u8>);
+
+impl PeerId {
+    /// Returns the raw identity.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PeerId(")?;
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl From<Vec<u8>> for PeerId {
+    fn from(id: Vec<u8>) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&[u8]> for PeerId {
+    fn from(id: &[u8]) -> Self {
+        Self(id.to_vec())
+    }
+}
+
+impl From<&Message> for PeerId {
+    fn from(id: &Message) -> Self {
+        Self(id.to_vec())
+    }
+}
+
+impl From<PeerId> for Message {
+    fn from(id: PeerId) -> Self {
+        Message::from(id.0)
+    }
+}
+
+/// ROUTER socket adapter which receives and sends `(PeerId, Multipart)` pairs.
+///
+/// The identity frame is removed from received messages and added to sent ones.
+///
+/// REQ peers put an envelope ending with an empty delimiter in front of their requests, which
+/// can be handled by the adapter as well, see [`delimited_envelopes`](Routed::delimited_envelopes).
+///
+/// With `ZMQ_ROUTER_MANDATORY` enabled, sending to an unknown peer fails with
+/// [`TmqError::HostUnreachable`].
+pub struct Routed {
+    router: Router,
+    delimited_envelopes: bool,
+    // Envelopes of the requests which haven't been replied to yet, oldest first.
+    envelopes: HashMap<PeerId, VecDeque<Vec<Message>>>,
+    // Peer of the message which is currently being sent.
+    destination: Option<PeerId>,
+}
+
+impl Routed {
+    /// Removes the envelopes of REQ peers from their requests (`false` by default).
+    ///
+    /// All frames up to and including the first empty delimiter of a request are treated as its
+    /// envelope. The envelopes are queued per peer, and every message sent to the peer gets the
+    /// envelope of its oldest request which hasn't been replied to yet, so replies have to be
+    /// sent in the order of the requests of each peer. An envelope is only dequeued once the
+    /// reply has been sent, so a failed reply can be sent again.
+    ///
+    /// Only enable this if all peers use delimiters, messages of DEALER peers without one are cut
+    /// at their first empty frame otherwise.
+    pub fn delimited_envelopes(mut self, value: bool) -> Self {
+        self.delimited_envelopes = value;
+        self
+    }
+
+    /// Returns a reference to the inner ROUTER socket.
+    pub fn get_ref(&self) -> &Router {
+        &self.router
+    }
+
+    /// Returns the inner ROUTER socket.
+    pub fn into_inner(self) -> Router {
+        self.router
+    }
+
+    /// Setter for the `ZMQ_ROUTER_MANDATORY` option.
+    pub fn set_router_mandatory(&self, value: bool) -> Result<()> {
+        self.router.set_router_mandatory(value)
+    }
+
+    /// Setter for the `ZMQ_ROUTER_HANDOVER` option.
+    pub fn set_router_handover(&self, value: bool) -> Result<()> {
+        self.router.set_router_handover(value)
+    }
+
+    fn map_send_result(&mut self, result: Poll<Result<()>>) -> Poll<Result<()>> {
+        match result {
+            Poll::Ready(Ok(())) => {
+                // The reply has been sent, so its request is done.
+                if let Some(peer) = self.destination.take() {
+                    if let Some(queue) = self.envelopes.get_mut(&peer) {
+                        queue.pop_front();
+                        if queue.is_empty() {
+                            self.envelopes.remove(&peer);
+                        }
+                    }
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(TmqError::Zmq(zmq::Error::EHOSTUNREACH))) => {
+                let peer = self.destination.take().unwrap_or_else(|| PeerId(vec![]));
+                Poll::Ready(Err(TmqError::HostUnreachable(peer)))
+            }
+            Poll::Ready(Err(e)) => {
+                self.destination = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl AsZmqSocket for Routed {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.router.get_socket()
+    }
+}
+
+impl Stream for Routed {
+    type Item = Result<(PeerId, Multipart)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut multipart = match ready!(Pin::new(&mut self.router).poll_next(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            let peer = match multipart.pop_front() {
+                Some(id) => PeerId::from(&id),
+                None => {
+                    log::warn!("Ignoring ROUTER message without identity");
+                    continue;
+                }
+            };
+            if self.delimited_envelopes {
+                let delimiter = multipart.iter().position(|frame| frame.is_empty());
+                match delimiter {
+                    Some(delimiter) => {
+                        let envelope = multipart.0.drain(..=delimiter).collect();
+                        self.envelopes
+                            .entry(peer.clone())
+                            .or_default()
+                            .push_back(envelope);
+                    }
+                    None => {
+                        // Only queued behind other envelopes, so that the replies stay in order.
+                        if let Some(queue) = self.envelopes.get_mut(&peer) {
+                            queue.push_back(Vec::new());
+                        }
+                    }
+                }
+            }
+            return Poll::Ready(Some(Ok((peer, multipart))));
+        }
+    }
+}
+
+impl Sink<(PeerId, Multipart)> for Routed {
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let result = Sink::<Multipart>::poll_ready(Pin::new(&mut self.router), cx);
+        self.map_send_result(result)
+    }
+
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (peer, mut multipart): (PeerId, Multipart),
+    ) -> Result<()> {
+        // The envelope is copied, so that it's still there if the reply fails.
+        let envelope = self.envelopes.get(&peer).and_then(VecDeque::front);
+        for frame in envelope.into_iter().flatten().rev() {
+            multipart.push_front(Message::from(&frame[..]));
+        }
+        multipart.push_front(peer.clone().into());
+        self.destination = Some(peer);
+        Pin::new(&mut self.router).start_send(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let result = Sink::<Multipart>::poll_flush(Pin::new(&mut self.router), cx);
+        self.map_send_result(result)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let result = Sink::<Multipart>::poll_close(Pin::new(&mut self.router), cx);
+        self.map_send_result(result)
+    }
+}
+
+/// Event reported by a [`PeerTracker`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PeerEvent {
+    /// A peer has connected.
+    Connected(PeerId),
+    /// A peer has disconnected.
+    Disconnected(PeerId),
+    /// A regular message was received from a peer, with the identity frame removed.
+    Message(PeerId, Multipart),
+}
+
+/// ROUTER socket adapter which keeps track of the connected peers.
+///
+/// With `ZMQ_ROUTER_NOTIFY` (see
+/// [`SocketBuilder::set_router_notify`](../struct.SocketBuilder.html#method.set_router_notify)),
+/// libzmq delivers a message consisting of the peer identity and a single empty frame whenever a
+/// peer connects or disconnects. Connect and disconnect notifications look the same, so they are
+/// told apart by the enabled flags:
+///
+/// * with only [`NOTIFY_DISCONNECT`], every notification means that the peer has disconnected,
+/// * with both flags, a notification of an unknown peer means it has connected and a notification
+///   of a connected peer that it has disconnected.
+///
+/// [`NOTIFY_DISCONNECT`] is required, see [`Router::track_peers`].
+///
+/// A regular message of an unknown peer is reported as [`PeerEvent::Connected`] before the
+/// message itself, so peers are tracked with only `NOTIFY_DISCONNECT` as well.
+/// With `ZMQ_ROUTER_HANDOVER`, a peer which reconnects before its old connection has been closed
+/// is reported as disconnected, and as connected again with its next message.
+///
+/// Peers shouldn't send messages consisting of a single empty frame themselves, as these can't be
+/// told apart from notifications.
+///
+/// Messages can be sent through the tracker, it is a `Sink` like the [`Router`].
+pub struct PeerTracker {
+    router: Router,
+    notify: i32,
+    peers: HashSet<PeerId>,
+    // Message which is reported after the connection of its unknown peer.
+    pending: Option<PeerEvent>,
+}
+
+impl PeerTracker {
+    /// Returns `true` if the peer is currently connected.
+    pub fn is_connected(&self, peer: &PeerId) -> bool {
+        self.peers.contains(peer)
+    }
+
+    /// Returns the currently connected peers.
+    pub fn peers(&self) -> &HashSet<PeerId> {
+        &self.peers
+    }
+
+    /// Returns a reference to the inner ROUTER socket.
+    pub fn get_ref(&self) -> &Router {
+        &self.router
+    }
+
+    /// Returns the inner ROUTER socket.
+    pub fn into_inner(self) -> Router {
+        self.router
+    }
+}
+
+impl AsZmqSocket for PeerTracker {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.router.get_socket()
+    }
+}
+
+impl Stream for PeerTracker {
+    type Item = Result<PeerEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if let Some(event) = self.pending.take() {
+            return Poll::Ready(Some(Ok(event)));
+        }
+
+        loop {
+            let mut multipart = match ready!(Pin::new(&mut self.router).poll_next(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            let peer = match multipart.pop_front() {
+                Some(id) => PeerId::from(&id),
+                None => {
+                    log::warn!("Ignoring ROUTER message without identity");
+                    continue;
+                }
+            };
+
+            let event = if multipart.len() == 1 && multipart[0].is_empty() {
+                let connected = self.notify & NOTIFY_CONNECT != 0 && !self.peers.contains(&peer);
+                if connected {
+                    self.peers.insert(peer.clone());
+                    PeerEvent::Connected(peer)
+                } else {
+                    self.peers.remove(&peer);
+                    PeerEvent::Disconnected(peer)
+                }
+            } else if self.peers.insert(peer.clone()) {
+                self.pending = Some(PeerEvent::Message(peer.clone(), multipart));
+                PeerEvent::Connected(peer)
+            } else {
+                PeerEvent::Message(peer, multipart)
+            };
+            return Poll::Ready(Some(Ok(event)));
+        }
+    }
+}
+
+impl<T: SinkItem> Sink<T> for PeerTracker {
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<T>::poll_ready(Pin::new(&mut self.router), cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<()> {
+        Pin::new(&mut self.router).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<T>::poll_flush(Pin::new(&mut self.router), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<T>::poll_close(Pin::new(&mut self.router), cx)
+    }
 }
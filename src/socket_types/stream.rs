@@ -0,0 +1,522 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, Either},
+    ready, Sink, SinkExt, Stream, StreamExt,
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{
+    comm::SenderReceiver,
+    context::OwnedSocket,
+    poll::ZmqPoller,
+    router::PeerId,
+    socket::{set_raw_bytes_option, set_raw_option},
+    AsContext, BytesMultipart, FromZmqSocket, Multipart, Result, SocketBuilder, TmqError,
+};
+
+/// Create a builder for a STREAM socket.
+pub fn stream(context: &impl AsContext) -> SocketBuilder<RawStream> {
+    SocketBuilder::new(context, zmq::SocketType::STREAM)
+}
+
+impl SocketBuilder<RawStream> {
+    /// Setter for the `ZMQ_STREAM_NOTIFY` option.
+    ///
+    /// The option is enabled by default. Without it, [`StreamEvent::Connected`] and
+    /// [`StreamEvent::Disconnected`] aren't reported and the connections of [`Connections`]
+    /// are never accepted nor closed by their peers.
+    pub fn set_stream_notify(self, value: bool) -> Self {
+        self.apply(|socket| set_raw_option(socket, zmq_sys::ZMQ_STREAM_NOTIFY, value as i32))
+    }
+}
+
+/// Asynchronous STREAM socket, which exchanges raw data with plain TCP peers.
+///
+/// Every TCP connection of the socket is identified by a [`PeerId`]. The socket is a `Stream` of
+/// [`StreamEvent`]s, which report connecting and disconnecting peers and the data received from
+/// them. Data is sent to a peer through the `Sink` of `(PeerId, Bytes)` pairs, sending empty
+/// `Bytes` closes the connection.
+///
+/// Connections closed through the `Sink` aren't reported as disconnected. libzmq only completes
+/// closing a connection while the socket is in use, so the socket should keep being polled.
+///
+/// The data isn't framed in any way, it is received in chunks as they arrive from the network.
+/// See [`into_connections`](#method.into_connections) for an `AsyncRead + AsyncWrite`
+/// connection per peer.
+pub struct RawStream {
+    inner: SenderReceiver,
+    peers: HashSet<PeerId>,
+}
+
+impl FromZmqSocket<RawStream> for RawStream {
+    fn from_zmq_socket(socket: OwnedSocket) -> Result<Self> {
+        Ok(Self {
+            inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
+            peers: HashSet::new(),
+        })
+    }
+}
+
+impl_wrapper!(RawStream, SenderReceiver, inner);
+
+impl RawStream {
+    /// Connects to a TCP endpoint and returns the identity of the new connection.
+    ///
+    /// The connection is established in the background and reported by
+    /// [`StreamEvent::Connected`] once it is up.
+    pub fn connect_peer(&mut self, endpoint: &str) -> Result<PeerId> {
+        static NEXT_PEER: AtomicU64 = AtomicU64::new(1);
+
+        let peer =
+            PeerId::from(format!("tmq-{}", NEXT_PEER.fetch_add(1, Ordering::Relaxed)).into_bytes());
+        let socket = self.inner.poller.get_socket_mut();
+        set_raw_bytes_option(socket, zmq_sys::ZMQ_CONNECT_ROUTING_ID, peer.as_bytes())?;
+        socket.connect(endpoint)?;
+        socket.add_endpoint(endpoint);
+        Ok(peer)
+    }
+
+    /// Returns `true` if the peer is currently connected.
+    pub fn is_connected(&self, peer: &PeerId) -> bool {
+        self.peers.contains(peer)
+    }
+
+    /// Returns the currently connected peers.
+    pub fn peers(&self) -> &HashSet<PeerId> {
+        &self.peers
+    }
+
+    /// Turns the socket into a [`Connections`] handle, which provides a [`Connection`] for every
+    /// peer.
+    ///
+    /// The socket is driven by a task spawned on the current Tokio runtime.
+    pub fn into_connections(self) -> Connections {
+        let (commands, receiver) = mpsc::channel(CONNECTION_BUFFER);
+        let (accepted, incoming) = mpsc::channel(ACCEPT_BUFFER);
+        tokio::spawn(drive(self, receiver, commands.clone(), accepted));
+        Connections { commands, incoming }
+    }
+}
+
+/// Event reported by a [`RawStream`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A peer has connected.
+    Connected(PeerId),
+    /// A peer has disconnected.
+    Disconnected(PeerId),
+    /// A chunk of data was received from a peer.
+    Data(PeerId, Bytes),
+}
+
+impl Stream for RawStream {
+    type Item = Result<StreamEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            let multipart = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            if multipart.len() != 2 {
+                log::warn!("Ignoring STREAM message with {} frames", multipart.len());
+                continue;
+            }
+
+            let mut frames = multipart.into_bytes_frames();
+            let data = frames.pop().unwrap();
+            let peer = PeerId::from(&frames[0][..]);
+
+            // An empty frame is a notification, the first one of a peer means it has connected.
+            let event = if !data.is_empty() {
+                StreamEvent::Data(peer, data)
+            } else if self.peers.remove(&peer) {
+                StreamEvent::Disconnected(peer)
+            } else {
+                self.peers.insert(peer.clone());
+                StreamEvent::Connected(peer)
+            };
+            return Poll::Ready(Some(Ok(event)));
+        }
+    }
+}
+
+impl Sink<(PeerId, Bytes)> for RawStream {
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Multipart>::poll_ready(Pin::new(&mut self.inner), cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, (peer, data): (PeerId, Bytes)) -> Result<()> {
+        // No notification is received for connections closed by the socket.
+        if data.is_empty() {
+            self.peers.remove(&peer);
+        }
+        let multipart = BytesMultipart::from(vec![Bytes::copy_from_slice(peer.as_bytes()), data]);
+        Pin::new(&mut self.inner).start_send(multipart)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Multipart>::poll_flush(Pin::new(&mut self.inner), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Multipart>::poll_close(Pin::new(&mut self.inner), cx)
+    }
+}
+
+// Number of writes which can be queued by the connections of a socket.
+const CONNECTION_BUFFER: usize = 16;
+
+// Number of accepted connections which can wait for the `Connections` to be polled.
+const ACCEPT_BUFFER: usize = 16;
+
+// Number of received chunks which can wait for a connection to be read.
+const READ_BUFFER: usize = 16;
+
+enum Command {
+    Send(PeerId, Bytes),
+    Connect(String, oneshot::Sender<Result<Connection>>),
+    Close(PeerId),
+    // Wakes the driver after the `Connections` have been dropped.
+    Detach,
+}
+
+/// Handle of a [`RawStream`] which accepts and opens [`Connection`]s.
+///
+/// It is a `Stream` of the connections accepted from peers. Dropping it rejects further peers,
+/// the socket is closed once the handle and all of its connections have been dropped.
+///
+/// Only a few accepted connections and received chunks of data are buffered. While the handle
+/// or a connection isn't polled and its buffer is full, the socket stops reading, which holds
+/// back all connections of the socket.
+pub struct Connections {
+    commands: mpsc::Sender<Command>,
+    incoming: mpsc::Receiver<Connection>,
+}
+
+impl Connections {
+    /// Connects to a TCP endpoint.
+    ///
+    /// The connection is established in the background, data written to it before that is
+    /// queued by the socket.
+    pub async fn connect(&self, endpoint: &str) -> Result<Connection> {
+        let (sender, receiver) = oneshot::channel();
+        self.commands
+            .clone()
+            .send(Command::Connect(endpoint.to_owned(), sender))
+            .await
+            .map_err(|_| TmqError::Closed)?;
+        receiver.await.map_err(|_| TmqError::Closed)?
+    }
+}
+
+impl Stream for Connections {
+    type Item = Connection;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for Connections {
+    fn drop(&mut self) {
+        self.incoming.close();
+        // A fresh sender always has room for one message.
+        let _ = self.commands.clone().try_send(Command::Detach);
+    }
+}
+
+/// A single TCP connection of a [`RawStream`], usable as `AsyncRead + AsyncWrite`.
+///
+/// Reading returns EOF once the peer has disconnected, writing fails with a `BrokenPipe` error.
+/// Shutting down or dropping the connection closes it. Connections can't be half-closed, the whole connection is closed as soon as either
+/// side shuts down writing.
+pub struct Connection {
+    peer: PeerId,
+    chunk: Bytes,
+    data: mpsc::Receiver<Bytes>,
+    commands: mpsc::Sender<Command>,
+    closed: bool,
+    // Set once the socket has forgotten the connection, e.g. because the peer has disconnected.
+    gone: Arc<AtomicBool>,
+}
+
+impl Connection {
+    fn new(peer: PeerId, commands: mpsc::Sender<Command>) -> (Self, Link) {
+        let (sender, data) = mpsc::channel(READ_BUFFER);
+        let gone = Arc::new(AtomicBool::new(false));
+        let connection = Self {
+            peer,
+            chunk: Bytes::new(),
+            data,
+            commands,
+            closed: false,
+            gone: gone.clone(),
+        };
+        let link = Link { data: sender, gone };
+        (connection, link)
+    }
+
+    /// Returns the identity of the connection.
+    pub fn peer(&self) -> &PeerId {
+        &self.peer
+    }
+
+    fn poll_command(&mut self, cx: &mut Context<'_>, command: Command) -> Poll<io::Result<()>> {
+        ready!(self.commands.poll_ready(cx)).map_err(broken_pipe)?;
+        self.commands.start_send(command).map_err(broken_pipe)?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn broken_pipe<E>(_: E) -> io::Error {
+    io::ErrorKind::BrokenPipe.into()
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.chunk.is_empty() {
+            match ready!(self.data.poll_next_unpin(cx)) {
+                Some(chunk) => self.chunk = chunk,
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let len = buf.remaining().min(self.chunk.len());
+        buf.put_slice(&self.chunk[..len]);
+        self.chunk.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.closed || self.gone.load(Ordering::Acquire) {
+            return Poll::Ready(Err(broken_pipe(())));
+        }
+        // An empty frame would close the connection.
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let command = Command::Send(self.peer.clone(), Bytes::copy_from_slice(buf));
+        ready!(self.poll_command(cx, command))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.closed {
+            let command = Command::Close(self.peer.clone());
+            ready!(self.poll_command(cx, command))?;
+            self.closed = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.closed {
+            // A fresh sender always has room for one message.
+            let _ = self
+                .commands
+                .clone()
+                .try_send(Command::Close(self.peer.clone()));
+        }
+    }
+}
+
+/// Runs the socket of a [`Connections`] handle, until the handle and all connections are gone.
+async fn drive(
+    socket: RawStream,
+    receiver: mpsc::Receiver<Command>,
+    commands: mpsc::Sender<Command>,
+    accepted: mpsc::Sender<Connection>,
+) {
+    let driver = Driver {
+        socket,
+        commands,
+        accepted,
+        connections: HashMap::new(),
+        pending: None,
+    };
+    driver.run(receiver).await
+}
+
+/// The driver's side of a [`Connection`].
+struct Link {
+    data: mpsc::Sender<Bytes>,
+    gone: Arc<AtomicBool>,
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.gone.store(true, Ordering::Release);
+    }
+}
+
+/// Item received from the socket which waits for room in the channel of its receiver.
+enum Pending {
+    Accept(Connection, Link),
+    Data(PeerId, Bytes),
+}
+
+struct Driver {
+    socket: RawStream,
+    commands: mpsc::Sender<Command>,
+    accepted: mpsc::Sender<Connection>,
+    connections: HashMap<PeerId, Link>,
+    // The socket isn't read while an item is pending, so that slow readers hold back the peers.
+    pending: Option<Pending>,
+}
+
+impl Driver {
+    async fn run(mut self, mut receiver: mpsc::Receiver<Command>) {
+        while !(self.accepted.is_closed() && self.connections.is_empty()) {
+            let next = {
+                let event = self.next_event();
+                futures::pin_mut!(event);
+                match future::select(event, receiver.next()).await {
+                    Either::Left((event, _)) => Either::Left(event),
+                    Either::Right((command, _)) => Either::Right(command),
+                }
+            };
+            let result = match next {
+                Either::Left(Some(Ok(Some(event)))) => self.handle_event(event).await,
+                Either::Left(Some(Ok(None))) => match self.pending.take() {
+                    Some(Pending::Accept(connection, link)) => self.accept(connection, link).await,
+                    Some(Pending::Data(peer, data)) => {
+                        self.deliver(peer, data);
+                        Ok(())
+                    }
+                    None => Ok(()),
+                },
+                Either::Left(Some(Err(e))) => Err(e),
+                Either::Left(None) => return,
+                Either::Right(Some(command)) => self.handle_command(command).await,
+                Either::Right(None) => return,
+            };
+            match result {
+                // The peer has disconnected before its data or close could be sent.
+                Ok(()) | Err(TmqError::Zmq(zmq::Error::EHOSTUNREACH)) => {}
+                Err(e) => {
+                    log::error!("Error on STREAM socket: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the next event of the socket, or `None` once the pending item can be delivered.
+    async fn next_event(&mut self) -> Option<Result<Option<StreamEvent>>> {
+        match &self.pending {
+            Some(Pending::Accept(..)) => {
+                // If the `Connections` are gone, the connection is rejected by `accept`.
+                let _ = future::poll_fn(|cx| self.accepted.poll_ready(cx)).await;
+            }
+            Some(Pending::Data(peer, _)) => {
+                if let Some(link) = self.connections.get_mut(peer) {
+                    let _ = future::poll_fn(|cx| link.data.poll_ready(cx)).await;
+                }
+            }
+            None => return self.socket.next().await.map(|event| event.map(Some)),
+        }
+        Some(Ok(None))
+    }
+
+    async fn handle_event(&mut self, event: StreamEvent) -> Result<()> {
+        match event {
+            StreamEvent::Connected(peer) => {
+                // Connections opened by `connect` are already known.
+                if !self.connections.contains_key(&peer) {
+                    let (connection, link) = Connection::new(peer, self.commands.clone());
+                    return self.accept(connection, link).await;
+                }
+            }
+            StreamEvent::Disconnected(peer) => {
+                self.connections.remove(&peer);
+            }
+            StreamEvent::Data(peer, data) => self.deliver(peer, data),
+        }
+        Ok(())
+    }
+
+    async fn handle_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Send(peer, data) => {
+                let result = self.socket.send((peer.clone(), data)).await;
+                if let Err(TmqError::Zmq(zmq::Error::EHOSTUNREACH)) = result {
+                    // The peer has disconnected, further writes fail.
+                    self.connections.remove(&peer);
+                }
+                result
+            }
+            Command::Connect(endpoint, reply) => {
+                let connection = self.socket.connect_peer(&endpoint).map(|peer| {
+                    let (connection, link) = Connection::new(peer.clone(), self.commands.clone());
+                    self.connections.insert(peer, link);
+                    connection
+                });
+                let _ = reply.send(connection);
+                Ok(())
+            }
+            Command::Close(peer) => {
+                self.connections.remove(&peer);
+                self.socket.send((peer, Bytes::new())).await
+            }
+            Command::Detach => Ok(()),
+        }
+    }
+
+    /// Hands an accepted connection to the `Connections`, or rejects the peer if they are gone.
+    async fn accept(&mut self, connection: Connection, link: Link) -> Result<()> {
+        let peer = connection.peer().clone();
+        match self.accepted.try_send(connection) {
+            Ok(()) => {
+                self.connections.insert(peer, link);
+                Ok(())
+            }
+            Err(e) if e.is_full() => {
+                self.pending = Some(Pending::Accept(e.into_inner(), link));
+                Ok(())
+            }
+            Err(_) => self.socket.send((peer, Bytes::new())).await,
+        }
+    }
+
+    /// Passes received data to its connection, data of closed connections is dropped.
+    fn deliver(&mut self, peer: PeerId, data: Bytes) {
+        if let Some(link) = self.connections.get_mut(&peer) {
+            if let Err(e) = link.data.try_send(data) {
+                if e.is_full() {
+                    self.pending = Some(Pending::Data(peer, e.into_inner()));
+                }
+            }
+        }
+    }
+}
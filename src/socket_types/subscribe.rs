@@ -1,6 +1,7 @@
-use zmq::Context as ZmqContext;
-
-use crate::{poll::ZmqPoller, socket::AsZmqSocket, FromZmqSocket, Receiver, SocketBuilder};
+use crate::{
+    context::OwnedSocket, poll::ZmqPoller, socket::AsZmqSocket, AsContext, FromZmqSocket, Receiver,
+    SocketBuilder,
+};
 
 /// Create a builder for a SUB socket.
 ///
@@ -29,17 +30,17 @@ use crate::{poll::ZmqPoller, socket::AsZmqSocket, FromZmqSocket, Receiver, Socke
 ///     Ok(())
 /// }
 /// ```
-pub fn subscribe(context: &ZmqContext) -> SocketBuilder<SubscribeWithoutTopic> {
+pub fn subscribe(context: &impl AsContext) -> SocketBuilder<SubscribeWithoutTopic> {
     SocketBuilder::new(context, zmq::SocketType::SUB)
 }
 
 /// SUB socket which is already bound or connected, but isn't yet subscribed to a topic.
 pub struct SubscribeWithoutTopic {
-    socket: zmq::Socket,
+    socket: OwnedSocket,
 }
 
 impl FromZmqSocket<SubscribeWithoutTopic> for SubscribeWithoutTopic {
-    fn from_zmq_socket(socket: zmq::Socket) -> crate::Result<Self> {
+    fn from_zmq_socket(socket: OwnedSocket) -> crate::Result<Self> {
         Ok(Self { socket })
     }
 }
@@ -60,6 +61,7 @@ pub struct Subscribe {
 }
 impl_wrapper!(Subscribe, Receiver, inner);
 impl_wrapper_stream!(Subscribe, inner);
+impl_buffered!(Subscribe, inner);
 
 impl Subscribe {
     /// Adds another topic to this subscriber.
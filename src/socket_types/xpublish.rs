@@ -0,0 +1,145 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Stream};
+use zmq::Message;
+
+use crate::{
+    comm::SenderReceiver, context::OwnedSocket, poll::ZmqPoller, socket::set_raw_option, AsContext,
+    FromZmqSocket, Result, SocketBuilder,
+};
+
+/// Create a builder for an XPUB socket.
+///
+/// ## Usage Example
+///
+/// ```rust,no_run
+/// use futures::{SinkExt, StreamExt};
+///
+/// use tmq::{xpublish, xpublish::SubscriptionEvent, Context, Result};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///
+///     let mut socket = xpublish(&Context::new())
+///         .set_xpub_verbose(true)
+///         .bind("tcp://127.0.0.1:7899")?;
+///
+///     while let Some(event) = socket.next().await {
+///         if let SubscriptionEvent::Subscribe(topic) = event? {
+///             socket.send(vec![topic, b"welcome".to_vec()]).await?;
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn xpublish(context: &impl AsContext) -> SocketBuilder<XPublish> {
+    SocketBuilder::new(context, zmq::SocketType::XPUB)
+}
+
+impl SocketBuilder<XPublish> {
+    /// Setter for the `ZMQ_XPUB_VERBOSE` option.
+    pub fn set_xpub_verbose(self, value: bool) -> Self {
+        self.apply(|socket| Ok(socket.set_xpub_verbose(value)?))
+    }
+
+    /// Setter for the `ZMQ_XPUB_VERBOSER` option.
+    pub fn set_xpub_verboser(self, value: bool) -> Self {
+        self.apply(|socket| set_raw_option(socket, zmq_sys::ZMQ_XPUB_VERBOSER, value as i32))
+    }
+
+    /// Setter for the `ZMQ_XPUB_NODROP` option.
+    pub fn set_xpub_nodrop(self, value: bool) -> Self {
+        self.apply(|socket| set_raw_option(socket, zmq_sys::ZMQ_XPUB_NODROP, value as i32))
+    }
+
+    /// Setter for the `ZMQ_XPUB_WELCOME_MSG` option.
+    pub fn set_xpub_welcome_msg(self, value: Option<&str>) -> Self {
+        self.apply(|socket| Ok(socket.set_xpub_welcome_msg(value)?))
+    }
+}
+
+/// A subscription change sent upstream by a SUB or XSUB peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionEvent {
+    /// A peer subscribed to the given topic.
+    Subscribe(Vec<u8>),
+    /// A peer unsubscribed from the given topic.
+    Unsubscribe(Vec<u8>),
+}
+
+impl SubscriptionEvent {
+    /// Returns the topic of this subscription change.
+    pub fn topic(&self) -> &[u8] {
+        match self {
+            SubscriptionEvent::Subscribe(topic) | SubscriptionEvent::Unsubscribe(topic) => topic,
+        }
+    }
+
+    /// Decodes a subscription frame, returning [`None`] if it doesn't start with `0x01` or `0x00`.
+    pub fn from_message(message: &Message) -> Option<Self> {
+        match message.split_first() {
+            Some((1, topic)) => Some(SubscriptionEvent::Subscribe(topic.to_vec())),
+            Some((0, topic)) => Some(SubscriptionEvent::Unsubscribe(topic.to_vec())),
+            _ => None,
+        }
+    }
+}
+
+impl From<SubscriptionEvent> for Message {
+    fn from(event: SubscriptionEvent) -> Self {
+        let (prefix, topic) = match event {
+            SubscriptionEvent::Subscribe(topic) => (1, topic),
+            SubscriptionEvent::Unsubscribe(topic) => (0, topic),
+        };
+        let mut frame = Vec::with_capacity(topic.len() + 1);
+        frame.push(prefix);
+        frame.extend_from_slice(&topic);
+        frame.into()
+    }
+}
+
+/// Asynchronous XPUB socket.
+///
+/// Published messages are sent through the `Sink` implementation, while subscription changes
+/// of the connected peers are received as a `Stream` of [`SubscriptionEvent`]s.
+pub struct XPublish {
+    inner: SenderReceiver,
+}
+
+impl FromZmqSocket<XPublish> for XPublish {
+    fn from_zmq_socket(socket: OwnedSocket) -> Result<Self> {
+        Ok(Self {
+            inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
+        })
+    }
+}
+
+impl_wrapper!(XPublish, SenderReceiver, inner);
+impl_wrapper_sink!(XPublish, inner);
+impl_buffered!(XPublish, inner);
+
+impl Stream for XPublish {
+    type Item = Result<SubscriptionEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            let multipart = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(multipart)) => multipart,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            let event = multipart
+                .0
+                .front()
+                .and_then(SubscriptionEvent::from_message);
+            match event {
+                Some(event) => return Poll::Ready(Some(Ok(event))),
+                None => log::warn!("Ignoring non-subscription message on XPUB socket"),
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use futures::SinkExt;
+use zmq::Message;
+
+use crate::{
+    comm::SenderReceiver, context::OwnedSocket, poll::ZmqPoller, xpublish::SubscriptionEvent,
+    AsContext, FromZmqSocket, Result, SocketBuilder,
+};
+
+/// Create a builder for an XSUB socket.
+///
+/// ## Usage Example
+///
+/// ```rust,no_run
+/// use futures::StreamExt;
+///
+/// use tmq::{xsubscribe, Context, Result};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///
+///     let mut socket = xsubscribe(&Context::new()).connect("tcp://127.0.0.1:7899")?;
+///     socket.subscribe(b"topic").await?;
+///
+///     while let Some(msg) = socket.next().await {
+///         println!(
+///             "Subscribe: {:?}",
+///             msg?.iter()
+///                 .map(|item| item.as_str().unwrap_or("invalid text"))
+///                 .collect::<Vec<&str>>()
+///         );
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn xsubscribe(context: &impl AsContext) -> SocketBuilder<XSubscribe> {
+    SocketBuilder::new(context, zmq::SocketType::XSUB)
+}
+
+/// Asynchronous XSUB socket.
+///
+/// Unlike [`Subscribe`](../subscribe/struct.Subscribe.html), subscriptions are sent upstream as
+/// messages, which makes it possible to forward them from an XPUB socket.
+/// Any multipart sent through the `Sink` implementation is passed to the publishers as is.
+pub struct XSubscribe {
+    inner: SenderReceiver,
+}
+
+impl FromZmqSocket<XSubscribe> for XSubscribe {
+    fn from_zmq_socket(socket: OwnedSocket) -> Result<Self> {
+        Ok(Self {
+            inner: SenderReceiver::new(ZmqPoller::from_zmq_socket(socket)?),
+        })
+    }
+}
+
+impl_wrapper!(XSubscribe, SenderReceiver, inner);
+impl_wrapper_sink!(XSubscribe, inner);
+impl_wrapper_stream!(XSubscribe, inner);
+impl_buffered!(XSubscribe, inner);
+
+impl XSubscribe {
+    /// Sends a subscription frame for the given topic to the publishers.
+    /// This doesn't remove the previously added topics.
+    pub async fn subscribe(&mut self, topic: &[u8]) -> Result<()> {
+        self.send_event(SubscriptionEvent::Subscribe(topic.to_vec()))
+            .await
+    }
+
+    /// Sends an unsubscription frame for the given topic to the publishers.
+    pub async fn unsubscribe(&mut self, topic: &[u8]) -> Result<()> {
+        self.send_event(SubscriptionEvent::Unsubscribe(topic.to_vec()))
+            .await
+    }
+
+    async fn send_event(&mut self, event: SubscriptionEvent) -> Result<()> {
+        self.inner.send(Message::from(event)).await
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{ready, Sink, Stream};
+use tokio::time::{Instant, Sleep};
+
+use crate::{socket::AsZmqSocket, Result, TmqError};
+
+/// Timeouts and deadlines for the `Stream` and `Sink` implementations of tmq sockets.
+///
+/// The sockets never block, so the `ZMQ_RCVTIMEO` and `ZMQ_SNDTIMEO` options have no effect
+/// on them. Instead, they are used as the default timeouts of
+/// [`recv_timeout`](#method.recv_timeout) and [`send_timeout`](#method.send_timeout) when
+/// `None` is passed. A negative value (the ZMQ default) means that there is no timeout.
+///
+/// An operation which doesn't complete in time fails with [`TmqError::Timeout`].
+///
+/// The extension is implemented for every socket type, as well as for the halves of split
+/// sockets and for [`SenderHandle`](crate::SenderHandle), which uses the `ZMQ_SNDTIMEO` of the
+/// socket at the time the handle was created.
+///
+/// ## Usage Example
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use tmq::{dealer, Context, Result, SocketTimeoutExt};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let mut socket = dealer(&Context::new())
+///         .set_rcvtimeo(1000)
+///         .connect("tcp://127.0.0.1:7899")?;
+///
+///     socket
+///         .send_timeout(vec!["hello"], Duration::from_millis(100))
+///         .await?;
+///     // Waits for at most a second, according to `ZMQ_RCVTIMEO`.
+///     let reply = socket.recv_timeout(None).await?;
+///     Ok(())
+/// }
+/// ```
+pub trait SocketTimeoutExt {
+    /// Returns the timeout used by [`recv_timeout`](#method.recv_timeout) if `None` is passed.
+    #[doc(hidden)]
+    fn default_recv_timeout(&self) -> Option<Duration>;
+
+    /// Returns the timeout used by [`send_timeout`](#method.send_timeout) if `None` is passed.
+    #[doc(hidden)]
+    fn default_send_timeout(&self) -> Option<Duration>;
+
+    /// Receives the next item, failing if it doesn't arrive within `timeout`.
+    ///
+    /// If `timeout` is `None`, the socket's `ZMQ_RCVTIMEO` is used.
+    fn recv_timeout<D: Into<Option<Duration>>>(&mut self, timeout: D) -> RecvTimeout<'_, Self> {
+        let timeout = timeout.into().or_else(|| self.default_recv_timeout());
+        RecvTimeout {
+            socket: self,
+            timer: Timer::new(timeout),
+        }
+    }
+
+    /// Sends an item, failing if it isn't sent within `timeout`.
+    ///
+    /// If `timeout` is `None`, the socket's `ZMQ_SNDTIMEO` is used.
+    ///
+    /// If the timeout expires after the socket has accepted the item, but before it has been
+    /// fully sent, the rest of it is sent by the next send or flush of the socket.
+    fn send_timeout<T, D: Into<Option<Duration>>>(
+        &mut self,
+        item: T,
+        timeout: D,
+    ) -> SendTimeout<'_, Self, T> {
+        let timeout = timeout.into().or_else(|| self.default_send_timeout());
+        SendTimeout {
+            socket: self,
+            item: Some(item),
+            timer: Timer::new(timeout),
+        }
+    }
+
+    /// Wraps the socket, so that every receive and send operation fails with
+    /// [`TmqError::Timeout`] once the `deadline` has passed.
+    ///
+    /// The deadline can be given as a `std::time::Instant` or a `tokio::time::Instant`.
+    fn with_deadline<I: Into<Instant>>(self, deadline: I) -> Deadline<Self>
+    where
+        Self: Sized,
+    {
+        Deadline {
+            inner: self,
+            timer: Timer::at(deadline.into()),
+        }
+    }
+}
+
+impl<S: AsZmqSocket + ?Sized> SocketTimeoutExt for S {
+    fn default_recv_timeout(&self) -> Option<Duration> {
+        default_timeout(self.get_socket().get_rcvtimeo())
+    }
+
+    fn default_send_timeout(&self) -> Option<Duration> {
+        default_timeout(self.get_socket().get_sndtimeo())
+    }
+}
+
+/// Converts a `ZMQ_RCVTIMEO` or `ZMQ_SNDTIMEO` value, negative values mean no timeout.
+pub(crate) fn default_timeout(millis: zmq::Result<i32>) -> Option<Duration> {
+    match millis {
+        Ok(millis) if millis >= 0 => Some(Duration::from_millis(millis as u64)),
+        _ => None,
+    }
+}
+
+/// Lazily started timer, so that it's only created inside of the runtime.
+struct Timer {
+    deadline: Option<Instant>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Timer {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            sleep: None,
+        }
+    }
+
+    fn at(deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            sleep: None,
+        }
+    }
+
+    /// Returns `Poll::Ready` once the deadline has passed.
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self.deadline {
+            Some(deadline) => self
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)))
+                .as_mut()
+                .poll(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Maps a pending operation to a timeout error once the deadline has passed.
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<Result<T>>) -> Poll<Result<T>> {
+        match poll {
+            Poll::Ready(result) => Poll::Ready(result),
+            Poll::Pending => self.poll_expired(cx).map(|_| Err(TmqError::Timeout)),
+        }
+    }
+}
+
+/// Future returned by [`SocketTimeoutExt::recv_timeout`].
+pub struct RecvTimeout<'a, S: ?Sized> {
+    socket: &'a mut S,
+    timer: Timer,
+}
+
+impl<S, T> Future for RecvTimeout<'_, S>
+where
+    S: Stream<Item = Result<T>> + Unpin + ?Sized,
+{
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { socket, timer } = &mut *self;
+        let poll = Pin::new(&mut **socket)
+            .poll_next(cx)
+            .map(|item| item.unwrap_or(Err(TmqError::Closed)));
+        timer.check(cx, poll)
+    }
+}
+
+/// Future returned by [`SocketTimeoutExt::send_timeout`].
+pub struct SendTimeout<'a, S: ?Sized, T> {
+    socket: &'a mut S,
+    item: Option<T>,
+    timer: Timer,
+}
+
+impl<S: ?Sized, T> Unpin for SendTimeout<'_, S, T> {}
+
+impl<S, T> SendTimeout<'_, S, T>
+where
+    S: Sink<T, Error = TmqError> + Unpin + ?Sized,
+{
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut socket = Pin::new(&mut *self.socket);
+        if self.item.is_some() {
+            ready!(socket.as_mut().poll_ready(cx))?;
+            socket.as_mut().start_send(self.item.take().unwrap())?;
+        }
+        socket.poll_flush(cx)
+    }
+}
+
+impl<S, T> Future for SendTimeout<'_, S, T>
+where
+    S: Sink<T, Error = TmqError> + Unpin + ?Sized,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let poll = self.poll_send(cx);
+        self.timer.check(cx, poll)
+    }
+}
+
+/// Socket adapter returned by [`SocketTimeoutExt::with_deadline`].
+pub struct Deadline<S> {
+    inner: S,
+    timer: Timer,
+}
+
+impl<S> Deadline<S> {
+    /// Returns the deadline of the operations.
+    pub fn deadline(&self) -> Instant {
+        self.timer.deadline.unwrap()
+    }
+
+    /// Returns a reference to the wrapped socket.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped socket.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped socket.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsZmqSocket> AsZmqSocket for Deadline<S> {
+    fn get_socket(&self) -> &zmq::Socket {
+        self.inner.get_socket()
+    }
+}
+
+impl<S, T> Stream for Deadline<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self { inner, timer } = &mut *self;
+        match Pin::new(inner).poll_next(cx) {
+            Poll::Ready(item) => Poll::Ready(item),
+            Poll::Pending => timer.poll_expired(cx).map(|_| Some(Err(TmqError::Timeout))),
+        }
+    }
+}
+
+impl<S, T> Sink<T> for Deadline<S>
+where
+    S: Sink<T, Error = TmqError> + Unpin,
+{
+    type Error = TmqError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self { inner, timer } = &mut *self;
+        let poll = Pin::new(inner).poll_ready(cx);
+        timer.check(cx, poll)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<()> {
+        Pin::new(&mut self.inner).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self { inner, timer } = &mut *self;
+        let poll = Pin::new(inner).poll_flush(cx);
+        timer.check(cx, poll)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self { inner, timer } = &mut *self;
+        let poll = Pin::new(inner).poll_close(cx);
+        timer.check(cx, poll)
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use zmq::Context;
+
+use tmq::{message_from_bytes, pair, BytesMultipart, Multipart, Result};
+use utils::generate_tcp_address;
+
+mod utils;
+
+#[test]
+fn message_takes_unique_bytes() {
+    let bytes = Bytes::from(vec![1; 1024]);
+    let data = bytes.as_ptr();
+
+    let message = message_from_bytes(bytes);
+    assert_eq!(message.as_ptr(), data);
+    assert_eq!(&message[..], &[1; 1024][..]);
+
+    assert!(message_from_bytes(Bytes::new()).is_empty());
+}
+
+#[test]
+fn message_copies_shared_bytes() {
+    let bytes = Bytes::from(vec![1; 1024]);
+    let mut message = message_from_bytes(bytes.clone());
+    assert_ne!(message.as_ptr(), bytes.as_ptr());
+
+    // Modifying the message leaves the shared memory alone.
+    message[0] = 2;
+    assert_eq!(bytes[0], 1);
+
+    static DATA: [u8; 64] = [0; 64];
+    let mut multipart = Multipart::from(Bytes::from_static(&DATA));
+    multipart.iter_mut().next().unwrap()[0] = 1;
+    assert_eq!(DATA[0], 0);
+}
+
+#[test]
+fn frames_share_messages() {
+    let multipart: Multipart = vec![vec![7u8; 1024], b"small".to_vec()].into();
+    let large = multipart[0].as_ptr();
+
+    let frames = multipart.into_bytes_frames();
+    assert_eq!(frames[0].as_ptr(), large);
+    assert_eq!(&frames[0][..], &[7u8; 1024][..]);
+    assert_eq!(&frames[1][..], b"small");
+}
+
+#[tokio::test]
+async fn send_bytes_frames() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sender = pair(&ctx).bind(&address)?;
+    let mut receiver = pair(&ctx).connect(&address)?;
+
+    let payload = Bytes::from(vec![42u8; 4 * 1024 * 1024]);
+    let frames = vec![Bytes::from_static(b"header"), payload.clone()];
+    sender
+        .send(
+            frames
+                .iter()
+                .cloned()
+                .map(message_from_bytes)
+                .collect::<Multipart>(),
+        )
+        .await?;
+    sender.send(payload.clone()).await?;
+
+    let received = receiver.next().await.unwrap()?.into_bytes_frames();
+    assert_eq!(received, frames);
+    let received = receiver.next().await.unwrap()?.into_bytes_frames();
+    assert_eq!(received, vec![payload]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_bytes_multipart() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sender = pair(&ctx).bind(&address)?;
+    let mut receiver = pair(&ctx).connect(&address)?;
+
+    let payload = Bytes::from(vec![42u8; 4 * 1024 * 1024]);
+    let frames = vec![Bytes::from_static(b"header"), Bytes::new(), payload.clone()];
+    sender.send(BytesMultipart::from(frames.clone())).await?;
+    sender.send(BytesMultipart::from(payload.clone())).await?;
+
+    let received = receiver.next().await.unwrap()?.into_bytes_frames();
+    assert_eq!(received, frames);
+    let received = receiver.next().await.unwrap()?.into_bytes_frames();
+    assert_eq!(received, vec![payload]);
+
+    Ok(())
+}
+
+struct Tracked {
+    data: Vec<u8>,
+    dropped: Arc<AtomicBool>,
+}
+
+impl AsRef<[u8]> for Tracked {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn bytes_multipart_is_not_copied() -> Result<()> {
+    let ctx = Context::new();
+    let mut sender = pair(&ctx).bind("inproc://bytes-not-copied")?;
+    let mut receiver = pair(&ctx).connect("inproc://bytes-not-copied")?;
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let bytes = Bytes::from_owner(Tracked {
+        data: vec![3; 1024],
+        dropped: dropped.clone(),
+    });
+    let data = bytes.as_ptr();
+    sender.send(BytesMultipart::from(bytes)).await?;
+
+    // The queued frame still refers to the memory of the bytes.
+    assert!(!dropped.load(Ordering::SeqCst));
+
+    let received = receiver.next().await.unwrap()?;
+    assert_eq!(received[0].as_ptr(), data);
+    assert!(!dropped.load(Ordering::SeqCst));
+
+    drop(received);
+    assert!(dropped.load(Ordering::SeqCst));
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use futures::{SinkExt, StreamExt};
+use zmq::Context;
+
+use tmq::{
+    codec::{Codec, Typed},
+    pair, Message, Multipart, Result, TmqError,
+};
+use utils::generate_tcp_address;
+
+mod utils;
+
+/// Encodes strings as a single UTF-8 frame.
+struct Utf8Codec;
+
+impl Codec for Utf8Codec {
+    type Item = String;
+
+    fn encode(&mut self, item: String) -> Result<Multipart> {
+        Ok(Message::from(item.as_str()).into())
+    }
+
+    fn decode(&mut self, multipart: Multipart) -> Result<String> {
+        String::from_utf8(multipart[0].to_vec()).map_err(|e| TmqError::Codec(Box::new(e)))
+    }
+}
+
+#[tokio::test]
+async fn custom_codec() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sender = Typed::new(pair(&ctx).bind(&address)?, Utf8Codec);
+    let mut receiver = Typed::new(pair(&ctx).connect(&address)?, Utf8Codec);
+
+    sender.send("hello".to_string()).await?;
+    assert_eq!(receiver.next().await.unwrap()?, "hello");
+
+    // Invalid messages are reported, but don't end the stream.
+    sender.get_mut().send(vec![&[0xff_u8][..]]).await?;
+    sender.send("world".to_string()).await?;
+    assert!(matches!(
+        receiver.next().await.unwrap(),
+        Err(TmqError::Codec(_))
+    ));
+    assert_eq!(receiver.next().await.unwrap()?, "world");
+
+    Ok(())
+}
+
+#[cfg(any(feature = "json", feature = "bincode", feature = "msgpack"))]
+mod serde_codecs {
+    use super::*;
+
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Job {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+    }
+
+    async fn roundtrip<C: Codec<Item = Job> + Clone + Unpin>(codec: C) -> Result<()> {
+        let address = generate_tcp_address();
+        let ctx = Context::new();
+        let mut sender = Typed::new(pair(&ctx).bind(&address)?, codec.clone());
+        let mut receiver = Typed::new(pair(&ctx).connect(&address)?, codec);
+
+        let job = Job {
+            id: 42,
+            name: "build".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+        };
+        sender.send(job.clone()).await?;
+        assert_eq!(receiver.next().await.unwrap()?, job);
+
+        // Multiple frames can't be decoded.
+        sender.get_mut().send(vec!["a", "b"]).await?;
+        assert!(matches!(
+            receiver.next().await.unwrap(),
+            Err(TmqError::Codec(_))
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_codec() -> Result<()> {
+        roundtrip(tmq::codec::JsonCodec::new()).await
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn bincode_codec() -> Result<()> {
+        roundtrip(tmq::codec::BincodeCodec::new()).await
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn msgpack_codec() -> Result<()> {
+        roundtrip(tmq::codec::MsgPackCodec::new()).await
+    }
+}
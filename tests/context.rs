@@ -0,0 +1,100 @@
+use futures::{SinkExt, StreamExt};
+
+use std::time::{Duration, Instant};
+use tmq::{pull, push, AsZmqSocket, Context, Result, TmqError};
+use utils::generate_tcp_address;
+
+mod utils;
+
+#[tokio::test]
+async fn shutdown_without_sockets() -> Result<()> {
+    Context::new().shutdown().await
+}
+
+#[tokio::test]
+async fn builder_options() -> Result<()> {
+    let ctx = Context::builder()
+        .set_io_threads(2)
+        .set_max_sockets(16)
+        .set_ipv6(true)
+        .build()?;
+
+    let sock = pull(&ctx).bind(&generate_tcp_address())?;
+    assert!(sock.get_socket().is_ipv6()?);
+
+    drop(sock);
+    ctx.shutdown().await
+}
+
+#[tokio::test]
+async fn tracks_open_sockets() -> Result<()> {
+    let address = "inproc://tracks-open-sockets";
+    let ctx = Context::new();
+    let mut receiver = pull(&ctx).bind(address)?;
+    let mut sender = push(&ctx).connect(address)?;
+
+    let mut open = ctx.open_sockets();
+    open.sort_by_key(|info| info.socket_type() as i32);
+    assert_eq!(open.len(), 2);
+    assert_eq!(open[0].socket_type(), zmq::SocketType::PULL);
+    assert_eq!(open[1].socket_type(), zmq::SocketType::PUSH);
+    assert!(open.iter().all(|info| info.endpoints() == [address]));
+
+    sender.send(vec!["hello"]).await?;
+    assert_eq!(receiver.next().await.unwrap()?, vec!["hello"].into());
+
+    drop(sender);
+    assert_eq!(ctx.open_sockets().len(), 1);
+    drop(receiver);
+    assert!(ctx.open_sockets().is_empty());
+
+    ctx.shutdown().await
+}
+
+#[tokio::test]
+async fn failed_bind_is_not_tracked() -> Result<()> {
+    let ctx = Context::new();
+    assert!(pull(&ctx).bind("invalid://endpoint").is_err());
+    assert!(ctx.open_sockets().is_empty());
+    ctx.shutdown().await
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn last_socket_terminates_context_in_background() -> Result<()> {
+    let ctx = Context::new();
+    let mut sender = push(&ctx)
+        .set_linger(500)
+        .connect(&generate_tcp_address())?;
+    // Nobody receives the message, so the socket lingers once it's closed.
+    sender.send(vec!["pending"]).await?;
+
+    drop(ctx);
+    let start = Instant::now();
+    drop(sender);
+    assert!(start.elapsed() < Duration::from_millis(250));
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_open_sockets() -> Result<()> {
+    let ctx = Context::new();
+    let sock = pull(&ctx).bind(&generate_tcp_address())?;
+
+    let shutdown = tokio::spawn(ctx.clone().shutdown());
+
+    // The runtime keeps running while the context waits for the socket.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!shutdown.is_finished());
+
+    // New sockets can't be created anymore.
+    assert!(matches!(
+        pull(&ctx).bind(&generate_tcp_address()),
+        Err(TmqError::Zmq(zmq::Error::ETERM))
+    ));
+
+    drop(sock);
+    tokio::time::timeout(Duration::from_secs(5), shutdown)
+        .await
+        .expect("Context was not terminated")
+        .unwrap()
+}
@@ -1,8 +1,8 @@
 use futures::{FutureExt, SinkExt, StreamExt, TryFutureExt};
 use zmq::{Context, SocketType};
 
-use std::thread::spawn;
-use tmq::{dealer, Multipart, Result};
+use std::{thread::spawn, time::Duration};
+use tmq::{dealer, reply_server, Multipart, Result, TmqError};
 use utils::{
     generate_tcp_address, hammer_receive, msg, send_multipart_repeated, send_multiparts, sync_echo,
     sync_receive_multipart_repeated, sync_receive_multiparts,
@@ -87,6 +87,37 @@ async fn receive_hammer() -> Result<()> {
     hammer_receive(sock, address, SocketType::DEALER).await
 }
 
+#[tokio::test]
+async fn receive_buffered_hammer() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let sock = dealer(&ctx).bind(&address)?;
+    hammer_receive(sock.buffered(1024), address, SocketType::DEALER).await
+}
+
+#[tokio::test]
+async fn buffered_echo() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let count = 100;
+
+    let echo = sync_echo(address.clone(), SocketType::DEALER, count);
+    let mut sock = dealer(&ctx).connect(&address)?.buffered_adaptive(1, 16);
+
+    for i in 0..count {
+        sock.send(vec![&i.to_string(), "hello"]).await?;
+    }
+    for i in 0..count {
+        assert_eq!(
+            sock.next().await.unwrap()?,
+            vec![msg(i.to_string().as_bytes()), msg(b"hello")].into()
+        );
+    }
+    echo.join().unwrap();
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn proxy_sequence() -> Result<()> {
     let address = generate_tcp_address();
@@ -221,3 +252,61 @@ async fn split_send_all() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn requester_concurrent_requests() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx).bind(&address)?.concurrency(10);
+    tokio::spawn(server.serve(|request: Multipart| async move {
+        // Later requests are answered first.
+        let index: u64 = std::str::from_utf8(&request[0]).unwrap().parse().unwrap();
+        tokio::time::sleep(Duration::from_millis(10 * (10 - index))).await;
+        Ok(request)
+    }));
+
+    let requester = dealer(&ctx).connect(&address)?.into_requester();
+    let tasks = (0..10u64)
+        .map(|i| {
+            let requester = requester.clone();
+            tokio::spawn(async move {
+                let index = i.to_string();
+                let reply = requester
+                    .request(vec![index.as_str(), "hello"].into())
+                    .await?;
+                assert_eq!(reply, vec![index.as_str(), "hello"].into());
+                Ok::<(), TmqError>(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap()?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn requester_discards_orphaned_reply() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx).bind(&address)?;
+    tokio::spawn(server.serve(|request: Multipart| async move {
+        if &request[0][..] == b"slow" {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+        Ok(request)
+    }));
+
+    let requester = dealer(&ctx).connect(&address)?.into_requester();
+    let result = requester
+        .request_timeout(vec!["slow"].into(), Duration::from_millis(50))
+        .await;
+    assert!(matches!(result, Err(TmqError::Timeout)));
+
+    // The late reply to the first request must not be mistaken for this one.
+    let reply = requester.request(vec!["fast"].into()).await?;
+    assert_eq!(reply, vec!["fast"].into());
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use tmq::{
+    patterns::lazy_pirate::LazyPirateClient, reply_server, router, Context, Multipart, Result,
+    TmqError,
+};
+
+fn spawn_echo_server(ctx: &Context, address: &str) -> Result<()> {
+    let server = reply_server(ctx).bind(address)?;
+    tokio::spawn(server.serve(|request| async move { Ok(request) }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_reply() -> Result<()> {
+    let address = "inproc://lazy-pirate-request-reply";
+    let ctx = Context::new();
+    spawn_echo_server(&ctx, address)?;
+
+    let mut client = LazyPirateClient::new(&ctx, vec![address]);
+    for i in 0..10 {
+        let i = i.to_string();
+        let reply = client.request(vec!["hello", &i]).await?;
+        assert_eq!(reply, Multipart::from(vec!["hello", &i]));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retries_exhausted() -> Result<()> {
+    let ctx = Context::new();
+    let mut client = LazyPirateClient::new(&ctx, vec!["inproc://lazy-pirate-nobody"])
+        .with_timeout(Duration::from_millis(50))
+        .with_retries(2);
+
+    let start = Instant::now();
+    let result = client.request(vec!["hello"]).await;
+    assert!(matches!(result, Err(TmqError::RetriesExhausted(3))));
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retry_after_lost_request() -> Result<()> {
+    let address = "inproc://lazy-pirate-lost-request";
+    let ctx = Context::new();
+    let mut server = router(&ctx).bind(address)?;
+    tokio::spawn(async move {
+        // Drops the first request, echoes the others.
+        server.next().await.unwrap()?;
+        while let Some(request) = server.next().await {
+            server.send(request?).await?;
+        }
+        Ok::<_, TmqError>(())
+    });
+
+    let mut client = LazyPirateClient::new(&ctx, vec![address])
+        .with_timeout(Duration::from_millis(100))
+        .with_retries(1);
+    let reply = client.request(vec!["hello"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["hello"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failover() -> Result<()> {
+    let dead = "inproc://lazy-pirate-failover-dead";
+    let live = "inproc://lazy-pirate-failover-live";
+    let ctx = Context::new();
+    spawn_echo_server(&ctx, live)?;
+
+    let mut client = LazyPirateClient::new(&ctx, vec![dead, live])
+        .with_timeout(Duration::from_millis(50))
+        .with_retries(1);
+    assert_eq!(client.endpoint(), dead);
+
+    let reply = client.request(vec!["hello"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["hello"]));
+    assert_eq!(client.endpoint(), live);
+
+    // The client stays with the server which has replied.
+    let reply = client.request(vec!["again"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["again"]));
+    assert_eq!(client.endpoint(), live);
+
+    Ok(())
+}
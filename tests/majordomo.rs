@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tmq::{
+    dealer,
+    dealer::Dealer,
+    patterns::majordomo::{MdpBroker, MdpClient, MdpWorker},
+    router, Context, Multipart, Result, TmqError,
+};
+use tokio::time::timeout;
+
+fn spawn_echo_worker(mut worker: MdpWorker) {
+    tokio::spawn(async move {
+        while let Some(request) = worker.next().await {
+            let mut request = request?;
+            let body = std::mem::take(request.body_mut());
+            request.partial(vec!["partial"])?;
+            request.reply(body)?;
+        }
+        Ok::<_, tmq::TmqError>(())
+    });
+}
+
+#[tokio::test]
+async fn request_reply() -> Result<()> {
+    let address = "inproc://mdp-request-reply";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+    spawn_echo_worker(MdpWorker::connect(&ctx, address, "echo")?);
+
+    let mut client = MdpClient::connect(&ctx, address)?;
+    for i in 0..10 {
+        let i = i.to_string();
+        let reply = client.request("echo", vec!["hello", &i]).await?;
+        assert_eq!(reply, Multipart::from(vec!["hello", &i]));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_wait_for_worker() -> Result<()> {
+    let address = "inproc://mdp-requests-wait-for-worker";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+
+    let mut client = MdpClient::connect(&ctx, address)?;
+    let request = tokio::spawn(async move { client.request("late", vec!["ping"]).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!request.is_finished());
+
+    spawn_echo_worker(MdpWorker::connect(&ctx, address, "late")?);
+    let reply = timeout(Duration::from_secs(5), request)
+        .await
+        .unwrap()
+        .unwrap()?;
+    assert_eq!(reply, Multipart::from(vec!["ping"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn mmi_service() -> Result<()> {
+    let address = "inproc://mdp-mmi-service";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+    spawn_echo_worker(MdpWorker::connect(&ctx, address, "echo")?);
+
+    let mut client = MdpClient::connect(&ctx, address)?;
+    client.request("echo", vec!["ping"]).await?;
+
+    let reply = client.request("mmi.service", vec!["echo"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["200"]));
+    let reply = client.request("mmi.service", vec!["missing"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["404"]));
+    let reply = client.request("mmi.unknown", vec!["echo"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["501"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wire_format() -> Result<()> {
+    let address = "inproc://mdp-wire-format";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+
+    let mut worker = dealer(&ctx).connect(address)?;
+    worker.send(vec![&b"MDPW02"[..], &[0x01], b"raw"]).await?;
+
+    let mut client = dealer(&ctx).connect(address)?;
+    client
+        .send(vec![&b"MDPC02"[..], &[0x01], b"raw", b"a", b"b"])
+        .await?;
+
+    let mut request = worker.next().await.unwrap()?;
+    assert_eq!(&request[0][..], b"MDPW02");
+    assert_eq!(&request[1][..], &[0x02]);
+    assert!(request[3].is_empty());
+    let client_address = request.0.drain(..4).nth(2).unwrap();
+    assert_eq!(request, Multipart::from(vec!["a", "b"]));
+
+    for command in [0x03, 0x04] {
+        worker
+            .send(vec![
+                &b"MDPW02"[..],
+                &[command],
+                &client_address,
+                b"",
+                b"reply",
+            ])
+            .await?;
+    }
+    for command in [0x02, 0x03] {
+        let reply = client.next().await.unwrap()?;
+        let command = [command];
+        let expected = vec![&b"MDPC02"[..], &command, b"raw", b"reply"];
+        assert_eq!(reply, Multipart::from(expected));
+    }
+
+    Ok(())
+}
+
+/// Receives the next message of a raw worker which isn't a heartbeat.
+async fn next_command(worker: &mut Dealer) -> Result<Multipart> {
+    loop {
+        let message = worker.next().await.unwrap()?;
+        if message[1][..] != [0x05] {
+            return Ok(message);
+        }
+    }
+}
+
+#[tokio::test]
+async fn broker_disconnects_workers_with_unexpected_replies() -> Result<()> {
+    let address = "inproc://mdp-unexpected-replies";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+
+    let mut client = dealer(&ctx).connect(address)?;
+    let mut worker = dealer(&ctx).connect(address)?;
+    let disconnect = Multipart::from(vec![&b"MDPW02"[..], &[0x06]]);
+
+    // A reply without a request.
+    worker.send(vec![&b"MDPW02"[..], &[0x01], b"echo"]).await?;
+    worker
+        .send(vec![&b"MDPW02"[..], &[0x04], b"victim", b"", b"forged"])
+        .await?;
+    assert_eq!(next_command(&mut worker).await?, disconnect);
+
+    // A second final reply to the same request.
+    worker.send(vec![&b"MDPW02"[..], &[0x01], b"echo"]).await?;
+    client
+        .send(vec![&b"MDPC02"[..], &[0x01], b"echo", b"ping"])
+        .await?;
+    let mut request = next_command(&mut worker).await?;
+    let client_address = request.0.drain(..4).nth(2).unwrap();
+    for _ in 0..2 {
+        worker
+            .send(vec![&b"MDPW02"[..], &[0x04], &client_address, b"", b"pong"])
+            .await?;
+    }
+    assert_eq!(next_command(&mut worker).await?, disconnect);
+
+    let reply = client.next().await.unwrap()?;
+    assert_eq!(
+        reply,
+        Multipart::from(vec![&b"MDPC02"[..], &[0x03], b"echo", b"pong"])
+    );
+    assert!(timeout(Duration::from_millis(50), client.next())
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn worker_heartbeats_and_reconnects() -> Result<()> {
+    let address = "inproc://mdp-worker-reconnects";
+    let ctx = Context::new();
+    let mut broker = router(&ctx).bind(address)?;
+    let mut worker =
+        MdpWorker::connect(&ctx, address, "echo")?.with_heartbeat(Duration::from_millis(20), 3);
+    tokio::spawn(async move { while worker.next().await.is_some() {} });
+
+    let mut ready = broker.next().await.unwrap()?;
+    let identity = ready.pop_front().unwrap();
+    assert_eq!(
+        ready,
+        Multipart::from(vec![&b"MDPW02"[..], &[0x01], b"echo"])
+    );
+
+    let mut heartbeat = broker.next().await.unwrap()?;
+    assert_eq!(heartbeat.pop_front().unwrap(), identity);
+    assert_eq!(heartbeat, Multipart::from(vec![&b"MDPW02"[..], &[0x05]]));
+
+    // The worker reconnects with a new socket when the broker goes silent.
+    loop {
+        let mut message = broker.next().await.unwrap()?;
+        let sender = message.pop_front().unwrap();
+        if sender != identity {
+            assert_eq!(
+                message,
+                Multipart::from(vec![&b"MDPW02"[..], &[0x01], b"echo"])
+            );
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn broker_expires_dead_workers() -> Result<()> {
+    let address = "inproc://mdp-broker-expires-workers";
+    let ctx = Context::new();
+    let broker = MdpBroker::bind(&ctx, address)?.with_heartbeat(Duration::from_millis(20), 2);
+    tokio::spawn(broker.run());
+
+    // A worker which never sends heartbeats.
+    let mut dead = dealer(&ctx).connect(address)?;
+    dead.send(vec![&b"MDPW02"[..], &[0x01], b"echo"]).await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let worker =
+        MdpWorker::connect(&ctx, address, "echo")?.with_heartbeat(Duration::from_millis(20), 2);
+    spawn_echo_worker(worker);
+
+    let mut client = MdpClient::connect(&ctx, address)?;
+    let reply = timeout(Duration::from_secs(5), client.request("echo", vec!["ping"])).await;
+    assert_eq!(reply.unwrap()?, Multipart::from(vec!["ping"]));
+
+    // The dead worker only got heartbeats.
+    while let Ok(message) = timeout(Duration::from_millis(10), dead.next()).await {
+        let message = message.unwrap()?;
+        assert_eq!(message, Multipart::from(vec![&b"MDPW02"[..], &[0x05]]));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn broker_requeues_requests_of_dead_workers() -> Result<()> {
+    let address = "inproc://mdp-broker-requeues-requests";
+    let ctx = Context::new();
+    let broker = MdpBroker::bind(&ctx, address)?.with_heartbeat(Duration::from_millis(20), 2);
+    tokio::spawn(broker.run());
+
+    // A worker which dies while handling a request.
+    let mut dead = dealer(&ctx).connect(address)?;
+    dead.send(vec![&b"MDPW02"[..], &[0x01], b"echo"]).await?;
+
+    let mut client = MdpClient::connect(&ctx, address)?;
+    let request = tokio::spawn(async move { client.request("echo", vec!["ping"]).await });
+    loop {
+        let message = dead.next().await.unwrap()?;
+        if message[1][..] == [0x02] {
+            break;
+        }
+    }
+
+    let worker =
+        MdpWorker::connect(&ctx, address, "echo")?.with_heartbeat(Duration::from_millis(20), 2);
+    spawn_echo_worker(worker);
+
+    let reply = timeout(Duration::from_secs(5), request).await;
+    assert_eq!(reply.unwrap().unwrap()?, Multipart::from(vec!["ping"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_timeout() -> Result<()> {
+    let address = "inproc://mdp-client-timeout";
+    let ctx = Context::new();
+    tokio::spawn(MdpBroker::bind(&ctx, address)?.run());
+
+    let mut client = MdpClient::connect(&ctx, address)?.with_timeout(Duration::from_millis(100));
+    let result = client.request("echo", vec!["first"]).await;
+    assert!(matches!(result, Err(TmqError::Timeout)));
+
+    // The late reply to the first request doesn't reach the new socket of the client.
+    spawn_echo_worker(MdpWorker::connect(&ctx, address, "echo")?);
+    let reply = client.request("echo", vec!["second"]).await?;
+    assert_eq!(reply, Multipart::from(vec!["second"]));
+
+    Ok(())
+}
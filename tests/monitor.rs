@@ -0,0 +1,93 @@
+use futures::StreamExt;
+use zmq::{Context, SocketType};
+
+use tmq::{pull, push, Message, Multipart, Result, SocketEvent};
+use utils::generate_tcp_address;
+
+mod utils;
+
+const ALL_EVENTS: i32 = zmq::SocketEvent::ALL as i32;
+
+#[tokio::test]
+async fn bind_events() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let (_sock, mut monitor) = pull(&ctx).monitored(ALL_EVENTS).bind(&address)?;
+
+    match monitor.next().await.unwrap()? {
+        SocketEvent::Listening { endpoint, .. } => assert_eq!(endpoint, address),
+        event => panic!("Unexpected event {:?}", event),
+    }
+
+    let peer = Context::new().socket(SocketType::PUSH).unwrap();
+    peer.connect(&address).unwrap();
+
+    match monitor.next().await.unwrap()? {
+        SocketEvent::Accepted { endpoint, .. } => assert_eq!(endpoint, address),
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match monitor.next().await.unwrap()? {
+        SocketEvent::HandshakeSucceeded { .. } => {}
+        event => panic!("Unexpected event {:?}", event),
+    }
+
+    drop(peer);
+    match monitor.next().await.unwrap()? {
+        SocketEvent::Disconnected { endpoint, .. } => assert_eq!(endpoint, address),
+        event => panic!("Unexpected event {:?}", event),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connect_events() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+
+    let peer = Context::new().socket(SocketType::PULL).unwrap();
+    peer.bind(&address).unwrap();
+
+    let events = zmq::SocketEvent::CONNECTED.to_raw() as i32;
+    let (sock, mut monitor) = push(&ctx).monitored(events).connect(&address)?;
+
+    match monitor.next().await.unwrap()? {
+        SocketEvent::Connected { endpoint, fd } => {
+            assert_eq!(endpoint, address);
+            assert!(fd >= 0);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+
+    // Closing the socket stops the monitor, which ends the stream.
+    drop(sock);
+    match monitor.next().await.unwrap()? {
+        SocketEvent::MonitorStopped { .. } => {}
+        event => panic!("Unexpected event {:?}", event),
+    }
+    assert!(monitor.next().await.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn decode_event() {
+    let mut frame = zmq::SocketEvent::CONNECT_RETRIED
+        .to_raw()
+        .to_ne_bytes()
+        .to_vec();
+    frame.extend_from_slice(&200u32.to_ne_bytes());
+    let multipart: Multipart = vec![Message::from(frame), Message::from("tcp://a:1")].into();
+
+    let event = SocketEvent::from_multipart(&multipart).unwrap();
+    assert_eq!(
+        event,
+        SocketEvent::ConnectRetried {
+            endpoint: "tcp://a:1".to_string(),
+            interval: 200
+        }
+    );
+    assert_eq!(event.endpoint(), "tcp://a:1");
+
+    assert_eq!(SocketEvent::from_multipart(&vec!["short"].into()), None);
+}
@@ -0,0 +1,134 @@
+use futures::{SinkExt, StreamExt};
+use zmq::Context;
+
+use std::time::Duration;
+use tmq::{dealer, pull, push, Multipart, Result};
+use tokio::time::timeout;
+use utils::generate_tcp_address;
+
+mod utils;
+
+const FRAMES: usize = 8;
+
+fn numbered_multipart(index: usize) -> Multipart {
+    (0..FRAMES)
+        .map(|frame| {
+            let mut data = vec![(frame % 256) as u8; 512 + frame * 64];
+            data[..8].copy_from_slice(&(index as u64).to_be_bytes());
+            zmq::Message::from(data)
+        })
+        .collect()
+}
+
+fn check_multipart(multipart: &Multipart) -> usize {
+    assert_eq!(multipart.len(), FRAMES, "Received a partial multipart");
+    let index = u64::from_be_bytes(multipart[0][..8].try_into().unwrap()) as usize;
+    assert_eq!(multipart, &numbered_multipart(index));
+    index
+}
+
+#[tokio::test]
+async fn tiny_hwm_push_pull() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let count = 2000;
+
+    let mut sender = push(&ctx).set_sndhwm(1).set_sndbuf(1024).bind(&address)?;
+    let mut receiver = pull(&ctx)
+        .set_rcvhwm(1)
+        .set_rcvbuf(1024)
+        .connect(&address)?;
+
+    // The sender is returned, so that it isn't closed while its last message is in flight.
+    let send = tokio::spawn(async move {
+        for i in 0..count {
+            sender.send(numbered_multipart(i)).await?;
+        }
+        Ok::<_, tmq::TmqError>(sender)
+    });
+
+    for i in 0..count {
+        let multipart = receiver.next().await.unwrap()?;
+        assert_eq!(check_multipart(&multipart), i);
+        if i % 100 == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+    let _sender = send.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tiny_hwm_cancelled_sends() -> Result<()> {
+    let address = "inproc://tiny-hwm-cancelled-sends";
+    let ctx = Context::new();
+    let count = 2000;
+
+    let mut sender = dealer(&ctx).set_sndhwm(1).bind(address)?;
+    let mut receiver = dealer(&ctx).set_rcvhwm(1).connect(address)?;
+
+    // Sends are abandoned while the socket is full. A message which was accepted by the sink
+    // is finished by the next send, all others are never started.
+    let send = tokio::spawn(async move {
+        for i in 0..count {
+            let _ = timeout(
+                Duration::from_micros(10),
+                sender.send(numbered_multipart(i)),
+            )
+            .await;
+        }
+        SinkExt::<Multipart>::flush(&mut sender).await?;
+        sender.send(vec!["done"]).await?;
+        Ok::<(), tmq::TmqError>(())
+    });
+
+    let mut last = None;
+    loop {
+        let multipart = receiver.next().await.unwrap()?;
+        if multipart.len() == 1 {
+            break;
+        }
+        let index = check_multipart(&multipart);
+        assert!(last.is_none_or(|last| index > last));
+        last = Some(index);
+    }
+    send.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn buffered_finishes_pending_multipart() -> Result<()> {
+    let address = "inproc://buffered-finishes-pending-multipart";
+    let ctx = Context::new();
+    let mut sender = push(&ctx).set_sndhwm(1).bind(address)?;
+    let mut receiver = pull(&ctx).set_rcvhwm(1).connect(address)?;
+
+    // Abandon a send while the socket is full, its multipart stays in the sink.
+    let mut pending = 0;
+    while timeout(
+        Duration::from_millis(10),
+        sender.send(numbered_multipart(pending)),
+    )
+    .await
+    .is_ok()
+    {
+        pending += 1;
+    }
+
+    let mut sender = sender.buffered(4);
+    let send = tokio::spawn(async move {
+        sender.send(vec!["done"]).await?;
+        Ok::<_, tmq::TmqError>(sender)
+    });
+
+    for i in 0..=pending {
+        let multipart = receiver.next().await.unwrap()?;
+        assert_eq!(check_multipart(&multipart), i);
+    }
+    assert_eq!(receiver.next().await.unwrap()?, vec!["done"].into());
+    let _sender = send.await.unwrap()?;
+
+    Ok(())
+}
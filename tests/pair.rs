@@ -1,9 +1,10 @@
 use zmq::{Context, SocketType};
 
-use futures::StreamExt;
+use futures::{SinkExt, StreamExt};
 use std::{
     sync::{Arc, Barrier},
     thread::spawn,
+    time::Duration,
 };
 use tmq::{pair, Result};
 use utils::{check_receive_multiparts, generate_tcp_address, hammer_receive, sync_send_multiparts};
@@ -87,3 +88,68 @@ async fn receive_delayed() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn split_read_while_write_blocked() -> Result<()> {
+    let address = "inproc://split-read-while-write";
+    let ctx = Context::new();
+    let count = 1000;
+
+    let (mut tx, mut rx) = pair(&ctx).set_sndhwm(1).bind(address)?.into_split();
+    let mut peer = pair(&ctx).set_rcvhwm(1).connect(address)?;
+
+    // The peer doesn't read yet, so the writer gets stuck on the high water mark.
+    let writer = tokio::spawn(async move {
+        for i in 0..count {
+            tx.send(vec![&i.to_string()].into()).await?;
+        }
+        Ok::<(), tmq::TmqError>(())
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!writer.is_finished());
+
+    // The pending writer must not prevent the reader from being woken up.
+    peer.send(vec!["ping"]).await?;
+    let received = tokio::time::timeout(Duration::from_secs(1), rx.next())
+        .await
+        .expect("Reader was not woken up")
+        .unwrap()?;
+    assert_eq!(received, vec!["ping"].into());
+
+    for i in 0..count {
+        assert_eq!(peer.next().await.unwrap()?, vec![&i.to_string()].into());
+    }
+    writer.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn split_send_keeps_read_wakeup() -> Result<()> {
+    let address = "inproc://split-send-keeps-read-wakeup";
+    let ctx = Context::new();
+
+    let (mut tx, mut rx) = pair(&ctx).bind(address)?.into_split();
+    let peer = ctx.socket(SocketType::PAIR)?;
+    peer.connect(address)?;
+
+    let reader = tokio::spawn(async move { rx.next().await });
+    // Let the reader park on the socket.
+    tokio::task::yield_now().await;
+    assert!(!reader.is_finished());
+
+    // Sending processes the socket's pending commands before the reader is woken up by the
+    // file descriptor, which consumes its signal.
+    peer.send("ping", 0)?;
+    tx.send(vec!["pong"].into()).await?;
+
+    let received = tokio::time::timeout(Duration::from_secs(1), reader)
+        .await
+        .expect("Reader was not woken up")
+        .unwrap()
+        .unwrap()?;
+    assert_eq!(received, vec!["ping"].into());
+    assert_eq!(peer.recv_multipart(0)?, vec![b"pong".to_vec()]);
+
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tmq::{
+    dealer,
+    patterns::paranoid_pirate::{PirateQueue, PirateWorker},
+    router, Context, Multipart, Result,
+};
+use tokio::time::timeout;
+
+fn spawn_echo_worker(mut worker: PirateWorker) {
+    tokio::spawn(async move {
+        while let Some(request) = worker.next().await {
+            let mut request = request?;
+            let body = std::mem::take(request.body_mut());
+            request.reply(body)?;
+        }
+        Ok::<_, tmq::TmqError>(())
+    });
+}
+
+#[tokio::test]
+async fn request_reply() -> Result<()> {
+    let frontend = "inproc://ppp-request-reply-frontend";
+    let backend = "inproc://ppp-request-reply-backend";
+    let ctx = Context::new();
+    tokio::spawn(PirateQueue::bind(&ctx, frontend, backend)?.run());
+    for _ in 0..2 {
+        spawn_echo_worker(PirateWorker::connect(&ctx, backend)?);
+    }
+
+    let mut client = dealer(&ctx).connect(frontend)?;
+    for i in 0..10 {
+        let i = i.to_string();
+        client.send(vec!["hello", &i]).await?;
+        let reply = client.next().await.unwrap()?;
+        assert_eq!(reply, Multipart::from(vec!["hello", &i]));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wire_format() -> Result<()> {
+    let frontend = "inproc://ppp-wire-format-frontend";
+    let backend = "inproc://ppp-wire-format-backend";
+    let ctx = Context::new();
+    let queue =
+        PirateQueue::bind(&ctx, frontend, backend)?.with_heartbeat(Duration::from_millis(20), 3);
+    tokio::spawn(queue.run());
+
+    let mut worker = dealer(&ctx).connect(backend)?;
+    worker.send(vec![&[0x01][..]]).await?;
+
+    let client = tmq::request(&ctx).connect(frontend)?;
+    let receiver = client.send(vec!["ping"].into()).await?;
+
+    // Heartbeats may arrive before the request, which keeps the REQ envelope.
+    let mut request = loop {
+        let message = worker.next().await.unwrap()?;
+        if message.len() > 1 {
+            break message;
+        }
+        assert_eq!(message, Multipart::from(vec![&[0x02][..]]));
+    };
+    assert_eq!(request.len(), 3);
+    assert!(request[1].is_empty());
+    assert_eq!(&request[2][..], b"ping");
+
+    request.pop_back();
+    request.push_back("pong".into());
+    worker.send(request).await?;
+    let (reply, _) = receiver.recv().await?;
+    assert_eq!(reply, Multipart::from(vec!["pong"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn worker_keeps_empty_frames_of_dealer_requests() -> Result<()> {
+    let frontend = "inproc://ppp-empty-frames-frontend";
+    let backend = "inproc://ppp-empty-frames-backend";
+    let ctx = Context::new();
+    tokio::spawn(PirateQueue::bind(&ctx, frontend, backend)?.run());
+
+    let mut worker = PirateWorker::connect(&ctx, backend)?;
+    tokio::spawn(async move {
+        while let Some(request) = worker.next().await {
+            let request = request?;
+            let len = request.body().len().to_string();
+            request.reply(vec![len.as_str()])?;
+        }
+        Ok::<_, tmq::TmqError>(())
+    });
+
+    let mut client = dealer(&ctx).connect(frontend)?;
+    client.send(vec!["a", "", "b"]).await?;
+    let reply = client.next().await.unwrap()?;
+    assert_eq!(reply, Multipart::from(vec!["3"]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn worker_survives_long_requests() -> Result<()> {
+    let address = "inproc://ppp-worker-long-requests";
+    let ctx = Context::new();
+    let mut queue = router(&ctx).bind(address)?;
+    let mut worker = PirateWorker::connect(&ctx, address)?
+        .with_heartbeat(Duration::from_millis(50), 2)
+        .with_reconnect_interval(Duration::from_millis(10), Duration::from_millis(10));
+    tokio::spawn(async move {
+        while let Some(request) = worker.next().await {
+            let request = request?;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            request.reply(vec!["done"])?;
+        }
+        Ok::<_, tmq::TmqError>(())
+    });
+
+    let mut ready = queue.next().await.unwrap()?;
+    let identity = ready.pop_front().unwrap();
+    let request = vec![&identity[..], b"client", b"", b"work"];
+    queue.send(request).await?;
+
+    // Like a queue, heartbeats are only sent to the worker once it is ready again.
+    let reply = loop {
+        let message = queue.next().await.unwrap()?;
+        if message.len() > 2 {
+            break message;
+        }
+    };
+    assert_eq!(
+        reply,
+        Multipart::from(vec![&identity[..], b"client", b"", b"done"])
+    );
+
+    for _ in 0..10 {
+        queue.send(vec![&identity[..], &[0x02]]).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    while let Ok(message) = timeout(Duration::from_millis(10), queue.next()).await {
+        let message = message.unwrap()?;
+        assert_eq!(message[0], identity, "Worker has reconnected");
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn worker_heartbeats_and_reconnects() -> Result<()> {
+    let address = "inproc://ppp-worker-reconnects";
+    let ctx = Context::new();
+    let mut queue = router(&ctx).bind(address)?;
+    let mut worker = PirateWorker::connect(&ctx, address)?
+        .with_heartbeat(Duration::from_millis(20), 3)
+        .with_reconnect_interval(Duration::from_millis(20), Duration::from_millis(80));
+    tokio::spawn(async move { while worker.next().await.is_some() {} });
+
+    let mut ready = queue.next().await.unwrap()?;
+    let identity = ready.pop_front().unwrap();
+    assert_eq!(ready, Multipart::from(vec![&[0x01][..]]));
+
+    let mut heartbeat = queue.next().await.unwrap()?;
+    assert_eq!(heartbeat.pop_front().unwrap(), identity);
+    assert_eq!(heartbeat, Multipart::from(vec![&[0x02][..]]));
+
+    // The worker reconnects with a new socket when the queue goes silent.
+    loop {
+        let mut message = queue.next().await.unwrap()?;
+        let sender = message.pop_front().unwrap();
+        if sender != identity {
+            assert_eq!(message, Multipart::from(vec![&[0x01][..]]));
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn queue_evicts_dead_workers() -> Result<()> {
+    let frontend = "inproc://ppp-evicts-workers-frontend";
+    let backend = "inproc://ppp-evicts-workers-backend";
+    let ctx = Context::new();
+    let queue =
+        PirateQueue::bind(&ctx, frontend, backend)?.with_heartbeat(Duration::from_millis(20), 2);
+    tokio::spawn(queue.run());
+
+    // A worker which never sends heartbeats.
+    let mut dead = dealer(&ctx).connect(backend)?;
+    dead.send(vec![&[0x01][..]]).await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let worker = PirateWorker::connect(&ctx, backend)?.with_heartbeat(Duration::from_millis(20), 2);
+    spawn_echo_worker(worker);
+
+    let mut client = dealer(&ctx).connect(frontend)?;
+    for _ in 0..3 {
+        client.send(vec!["ping"]).await?;
+        let reply = timeout(Duration::from_secs(5), client.next()).await;
+        assert_eq!(reply.unwrap().unwrap()?, Multipart::from(vec!["ping"]));
+    }
+
+    // The dead worker only got heartbeats.
+    while let Ok(message) = timeout(Duration::from_millis(10), dead.next()).await {
+        let message = message.unwrap()?;
+        assert_eq!(message, Multipart::from(vec![&[0x02][..]]));
+    }
+
+    Ok(())
+}
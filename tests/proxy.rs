@@ -0,0 +1,162 @@
+use futures::{SinkExt, StreamExt};
+use zmq::Context;
+
+use std::time::Duration;
+use tmq::{
+    dealer, pair,
+    proxy::{proxy, Proxy, ProxyCommand, ProxyStatistics},
+    router, Multipart, Result,
+};
+use tokio::time::timeout;
+use utils::generate_tcp_address;
+
+mod utils;
+
+async fn spawn_echo_worker(ctx: &Context, address: &str) -> Result<()> {
+    let mut worker = dealer(ctx).connect(address)?;
+    tokio::spawn(async move {
+        while let Some(Ok(msg)) = worker.next().await {
+            worker.send(msg).await.unwrap();
+        }
+    });
+    Ok(())
+}
+
+#[tokio::test]
+async fn forward_requests() -> Result<()> {
+    let frontend = generate_tcp_address();
+    let backend = generate_tcp_address();
+    let ctx = Context::new();
+
+    let router = router(&ctx).bind(&frontend)?;
+    let dealer_sock = dealer(&ctx).bind(&backend)?;
+    tokio::spawn(proxy(router, dealer_sock));
+
+    spawn_echo_worker(&ctx, &backend).await?;
+
+    let mut client = dealer(&ctx).connect(&frontend)?;
+    for i in 0..100 {
+        let index = i.to_string();
+        let msg = vec!["hello", &index];
+        client.send(msg.clone()).await?;
+        let response = client.next().await.unwrap()?;
+        assert_eq!(response, msg.into());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn capture_and_control() -> Result<()> {
+    let frontend = generate_tcp_address();
+    let backend = generate_tcp_address();
+    let ctx = Context::new();
+
+    let capture = pair(&ctx).bind("inproc://proxy-capture")?;
+    let mut capture_rx = pair(&ctx).connect("inproc://proxy-capture")?;
+    let control = pair(&ctx).bind("inproc://proxy-control")?;
+    let mut control_tx = pair(&ctx).connect("inproc://proxy-control")?;
+
+    let proxy = Proxy::new(router(&ctx).bind(&frontend)?, dealer(&ctx).bind(&backend)?)
+        .capture(capture)
+        .control(control);
+    let proxy = tokio::spawn(proxy.run());
+
+    spawn_echo_worker(&ctx, &backend).await?;
+
+    let mut client = dealer(&ctx).connect(&frontend)?;
+    client.send(vec!["hello"]).await?;
+    assert_eq!(client.next().await.unwrap()?, vec!["hello"].into());
+
+    // Both directions are captured, with the client identity in front.
+    for _ in 0..2 {
+        let captured = capture_rx.next().await.unwrap()?;
+        assert_eq!(captured.len(), 2);
+        assert_eq!(&captured[1][..], b"hello");
+    }
+
+    control_tx.send(ProxyCommand::Statistics).await?;
+    let stats = ProxyStatistics::from_multipart(&control_tx.next().await.unwrap()?).unwrap();
+    assert_eq!(stats.frontend.messages_in, 1);
+    assert_eq!(stats.frontend.messages_out, 1);
+    assert_eq!(stats.backend.messages_in, 1);
+    assert_eq!(stats.backend.messages_out, 1);
+    assert_eq!(stats.frontend.bytes_in, stats.backend.bytes_out);
+
+    // While paused, requests are not forwarded.
+    control_tx.send(ProxyCommand::Pause).await?;
+    control_tx.send(ProxyCommand::Statistics).await?;
+    control_tx.next().await.unwrap()?;
+    client.send(vec!["paused"]).await?;
+    assert!(timeout(Duration::from_millis(200), client.next())
+        .await
+        .is_err());
+
+    control_tx.send(ProxyCommand::Resume).await?;
+    assert_eq!(client.next().await.unwrap()?, vec!["paused"].into());
+
+    control_tx.send(ProxyCommand::Terminate).await?;
+    proxy.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn control_while_forwarding() -> Result<()> {
+    let frontend = generate_tcp_address();
+    let ctx = Context::new();
+
+    let control = pair(&ctx).bind("inproc://proxy-control-while-forwarding")?;
+    let mut control_tx = pair(&ctx).connect("inproc://proxy-control-while-forwarding")?;
+
+    // Without any peers, the backend can't take the forwarded message.
+    let backend = dealer(&ctx).set_linger(0).bind(&generate_tcp_address())?;
+    let proxy = Proxy::new(router(&ctx).bind(&frontend)?, backend).control(control);
+    let proxy = tokio::spawn(proxy.run());
+
+    let mut client = dealer(&ctx).connect(&frontend)?;
+    client.send(vec!["stuck"]).await?;
+
+    // The control socket is still served once the proxy is waiting for the backend.
+    loop {
+        control_tx.send(ProxyCommand::Statistics).await?;
+        let reply = timeout(Duration::from_secs(1), control_tx.next())
+            .await
+            .expect("Statistics weren't sent")
+            .unwrap()?;
+        if ProxyStatistics::from_multipart(&reply)
+            .unwrap()
+            .frontend
+            .messages_in
+            == 1
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    control_tx.send(ProxyCommand::Terminate).await?;
+    timeout(Duration::from_secs(1), proxy)
+        .await
+        .expect("Proxy wasn't terminated")
+        .unwrap()?;
+
+    Ok(())
+}
+
+#[test]
+fn statistics_frames() {
+    let mut stats = ProxyStatistics::default();
+    stats.frontend.messages_in = 1;
+    stats.frontend.bytes_in = 2;
+    stats.backend.messages_out = 3;
+    stats.backend.bytes_out = 4;
+
+    let multipart = Multipart::from(stats);
+    assert_eq!(multipart.len(), 8);
+    assert_eq!(ProxyStatistics::from_multipart(&multipart), Some(stats));
+    assert_eq!(
+        ProxyStatistics::from_multipart(&vec!["STATISTICS"].into()),
+        None
+    );
+}
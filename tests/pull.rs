@@ -1,9 +1,11 @@
 use zmq::{Context, SocketType};
 
-use futures::StreamExt;
+use futures::{future::poll_fn, Stream, StreamExt};
 use std::{
+    pin::Pin,
     sync::{Arc, Barrier},
     thread::spawn,
+    time::Duration,
 };
 use tmq::{pull, Result};
 use utils::{check_receive_multiparts, generate_tcp_address, hammer_receive, sync_send_multiparts};
@@ -95,3 +97,102 @@ async fn receive_delayed() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn idle_receiver_parks() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = pull(&ctx).bind(&address)?;
+
+    let peer = ctx.socket(SocketType::PUSH)?;
+    peer.connect(&address)?;
+
+    // Nothing is ever sent, so after the connection has been set up the task must stay parked
+    // instead of being woken up over and over.
+    let mut polls = 0;
+    let next = poll_fn(|cx| {
+        polls += 1;
+        Pin::new(&mut sock).poll_next(cx)
+    });
+    assert!(tokio::time::timeout(Duration::from_millis(500), next)
+        .await
+        .is_err());
+    assert!(polls < 10, "Idle receiver was polled {} times", polls);
+
+    Ok(())
+}
+
+/// Queues `count` messages for the socket before reading them, so that it never has to wait.
+/// Returns whether another task got to run in the meantime.
+async fn flooded_receive<S>(sock: S, ctx: &Context, address: &str, count: usize) -> Result<bool>
+where
+    S: Stream<Item = Result<tmq::Multipart>> + Unpin,
+{
+    let peer = ctx.socket(SocketType::PUSH)?;
+    peer.set_sndhwm(count as i32)?;
+    peer.connect(address)?;
+    for i in 0..count {
+        peer.send(&i.to_string(), 0)?;
+    }
+
+    let other = tokio::spawn(async {});
+    let mut sock = sock;
+    for i in 0..count {
+        assert_eq!(sock.next().await.unwrap()?, vec![&i.to_string()].into());
+    }
+    Ok(other.is_finished())
+}
+
+#[tokio::test]
+async fn flooded_receiver_yields() -> Result<()> {
+    let address = "inproc://flooded-receiver-yields";
+    let ctx = Context::new();
+    let sock = pull(&ctx).set_rcvhwm(1000).bind(address)?;
+
+    assert!(flooded_receive(sock, &ctx, address, 1000).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flooded_buffered_receiver_yields() -> Result<()> {
+    let address = "inproc://flooded-buffered-receiver-yields";
+    let ctx = Context::new();
+    let sock = pull(&ctx).set_rcvhwm(1000).bind(address)?;
+
+    assert!(flooded_receive(sock.buffered(64), &ctx, address, 1000).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn adaptive_batch_size() -> Result<()> {
+    let address = "inproc://adaptive-batch-size";
+    let ctx = Context::new();
+    let mut sock = pull(&ctx)
+        .set_rcvhwm(1000)
+        .bind(address)?
+        .buffered_adaptive(1, 256);
+
+    let peer = ctx.socket(SocketType::PUSH)?;
+    peer.set_sndhwm(1000)?;
+    peer.connect(address)?;
+
+    // A long burst grows the batch size up to the maximum.
+    for i in 0..1000 {
+        peer.send(&i.to_string(), 0)?;
+    }
+    for i in 0..1000 {
+        assert_eq!(sock.next().await.unwrap()?, vec![&i.to_string()].into());
+    }
+    assert_eq!(sock.batch_size(), 256);
+
+    // Single messages shrink it down to the minimum again.
+    for i in 0..10 {
+        peer.send(&i.to_string(), 0)?;
+        assert_eq!(sock.next().await.unwrap()?, vec![&i.to_string()].into());
+    }
+    assert_eq!(sock.batch_size(), 1);
+
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use zmq::{Context, SocketType};
 
-use futures::SinkExt;
-use std::{thread::spawn, time::Duration};
-use tmq::{push, Result};
+use futures::{SinkExt, StreamExt};
+use std::{collections::HashSet, thread::spawn, time::Duration};
+use tmq::{pull, push, Result, SenderHandle, TmqError};
 use tokio::time::timeout;
 use utils::{
     generate_tcp_address, msg, send_multipart_repeated, send_multiparts,
@@ -114,3 +114,119 @@ async fn send_delayed() -> Result<()> {
 
     Ok(())
 }
+
+fn assert_shareable<T: Clone + Send + Sync>() {}
+
+#[tokio::test]
+async fn handle_from_many_tasks() -> Result<()> {
+    assert_shareable::<SenderHandle>();
+
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut receiver = pull(&ctx).bind(&address)?;
+    let handle = push(&ctx).connect(&address)?.into_handle();
+
+    let tasks = (0..10)
+        .map(|task| {
+            let mut handle = handle.clone();
+            tokio::spawn(async move {
+                for i in 0..100 {
+                    handle.send(vec![&format!("{}-{}", task, i)]).await?;
+                }
+                Ok::<(), TmqError>(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap()?;
+    }
+
+    let mut received = HashSet::new();
+    for _ in 0..1000 {
+        let msg = receiver.next().await.unwrap()?;
+        received.insert(msg[0].as_str().unwrap().to_string());
+    }
+    assert_eq!(received.len(), 1000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_shutdown_drains_queue() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let handle = push(&ctx).connect(&address)?.into_handle();
+    let mut other = handle.clone();
+
+    // Nobody is connected yet, so the messages stay queued.
+    let mut sender = handle.clone();
+    for i in 0..50 {
+        sender.send(vec![&i.to_string()]).await?;
+    }
+
+    let receiver = spawn(move || {
+        let socket = Context::new().socket(SocketType::PULL).unwrap();
+        socket.bind(&address).unwrap();
+        for i in 0..50 {
+            assert_eq!(
+                socket.recv_multipart(0).unwrap(),
+                vec![i.to_string().into_bytes()]
+            );
+        }
+    });
+
+    handle.shutdown().await;
+    assert!(matches!(
+        other.send(vec!["late"]).await,
+        Err(TmqError::Closed)
+    ));
+
+    tokio::task::spawn_blocking(move || receiver.join().unwrap())
+        .await
+        .unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn buffered_send_all() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = push(&ctx).connect(&address)?.buffered(64);
+
+    let count = 10_000;
+    let thread =
+        sync_receive_multipart_repeated(address, SocketType::PULL, vec!["hello", "world"], count);
+
+    let mut messages =
+        futures::stream::iter((0..count).map(|_| Ok(tmq::Multipart::from(vec!["hello", "world"]))));
+    sock.send_all(&mut messages).await?;
+
+    tokio::task::spawn_blocking(move || thread.join().unwrap())
+        .await
+        .unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn buffered_waits_for_flush() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = push(&ctx).bind(&address)?.buffered(10);
+    let mut receiver = pull(&ctx).connect(&address)?;
+
+    for i in 0..3 {
+        sock.feed(vec![&i.to_string()]).await?;
+    }
+    assert!(timeout(Duration::from_millis(100), receiver.next())
+        .await
+        .is_err());
+
+    SinkExt::<tmq::Multipart>::flush(&mut sock).await?;
+    for i in 0..3 {
+        assert_eq!(receiver.next().await.unwrap()?, vec![&i.to_string()].into());
+    }
+
+    Ok(())
+}
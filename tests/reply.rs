@@ -1,5 +1,9 @@
-use std::thread::{spawn, JoinHandle};
-use tmq::{reply, Result};
+use futures::{SinkExt, StreamExt};
+use std::{
+    thread::{spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+use tmq::{dealer, reply, reply_server, request, AsZmqSocket, Multipart, Result, TmqError};
 use utils::generate_tcp_address;
 use zmq::Context;
 
@@ -47,6 +51,101 @@ async fn hammer_reply() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn server_replies() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx).bind(&address)?;
+    tokio::spawn(server.serve(|mut request| async move {
+        request.push_back("reply".into());
+        Ok(request)
+    }));
+
+    let mut requester = request(&ctx).connect(&address)?;
+    for i in 0..10 {
+        let msg = format!("Req#{}", i);
+        let receiver = requester.send(vec![msg.as_str()].into()).await?;
+        let (reply, sender) = receiver.recv().await?;
+        assert_eq!(reply, vec![msg.as_str(), "reply"].into());
+        requester = sender;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_handles_requests_concurrently() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx).bind(&address)?.concurrency(4);
+    tokio::spawn(server.serve(|request: Multipart| async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        Ok(request)
+    }));
+
+    let start = Instant::now();
+    let clients = (0..4)
+        .map(|_| sync_requester(address.clone(), 1, "concurrent"))
+        .collect::<Vec<_>>();
+    tokio::task::spawn_blocking(move || {
+        for client in clients {
+            client.join().unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    // Handled one at a time, the requests would take at least 1.2s.
+    assert!(start.elapsed() < Duration::from_millis(1000));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_receives_while_reply_is_blocked() -> Result<()> {
+    let address = "inproc://server-receives-while-reply-is-blocked";
+    // The sockets keep the context alive, so a failed test doesn't wait for the server task.
+    let ctx = tmq::Context::new();
+    let server = reply_server(&ctx)
+        .set_sndhwm(1)
+        .bind(address)?
+        .concurrency(16);
+    // Replies to a client which doesn't read them can't be sent once its queue is full.
+    server.get_socket().set_router_mandatory(true)?;
+    let (handled, mut handled_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(server.serve(move |request: Multipart| {
+        let handled = handled.clone();
+        async move {
+            handled
+                .send(request[0].as_str().unwrap().to_owned())
+                .unwrap();
+            Ok(request)
+        }
+    }));
+
+    let mut slow = dealer(&ctx).set_rcvhwm(1).connect(address)?;
+    for _ in 0..5 {
+        slow.send(vec!["", "slow"]).await?;
+    }
+    let timeout = Duration::from_secs(1);
+    for _ in 0..5 {
+        let next = tokio::time::timeout(timeout, handled_rx.recv()).await;
+        assert_eq!(next.unwrap().unwrap(), "slow");
+    }
+
+    let mut fast = dealer(&ctx).connect(address)?;
+    fast.send(vec!["", "fast"]).await?;
+    let next = tokio::time::timeout(timeout, handled_rx.recv()).await;
+    assert_eq!(next.unwrap().unwrap(), "fast");
+
+    for _ in 0..5 {
+        assert_eq!(slow.next().await.unwrap()?, vec!["", "slow"].into());
+    }
+    assert_eq!(fast.next().await.unwrap()?, vec!["", "fast"].into());
+
+    Ok(())
+}
+
 pub fn sync_requester(address: String, count: u64, part2: &'static str) -> JoinHandle<()> {
     spawn(move || {
         let socket = Context::new().socket(zmq::SocketType::REQ).unwrap();
@@ -55,10 +154,61 @@ pub fn sync_requester(address: String, count: u64, part2: &'static str) -> JoinH
         for i in 0..count {
             let msg = format!("Req#{}", i);
             socket
-                .send_multipart(&[msg.as_bytes(), part2.as_bytes()], 0)
+                .send_multipart([msg.as_bytes(), part2.as_bytes()], 0)
                 .unwrap();
             let received = socket.recv_multipart(0).unwrap();
             assert_eq!(&received[0], &msg.as_bytes());
         }
     })
 }
+
+#[tokio::test]
+async fn server_replies_to_failed_requests() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx)
+        .bind(&address)?
+        .error_reply(|_| vec!["failed"].into());
+    tokio::spawn(server.serve(|request: Multipart| async move {
+        match request[0].as_str() {
+            Some("fail") => Err(TmqError::Closed),
+            _ => Ok(request),
+        }
+    }));
+
+    let requester = request(&ctx).connect(&address)?;
+    let (reply, requester) = requester.send(vec!["fail"].into()).await?.recv().await?;
+    assert_eq!(reply, vec!["failed"].into());
+    let (reply, _) = requester.send(vec!["ok"].into()).await?.recv().await?;
+    assert_eq!(reply, vec!["ok"].into());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_keeps_empty_frames_of_dealer_requests() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let server = reply_server(&ctx)
+        .bind(&address)?
+        .delimited_envelopes(false);
+    tokio::spawn(server.serve(|request: Multipart| async move {
+        assert_eq!(request.len(), 3);
+        Ok(request)
+    }));
+
+    let mut client = dealer(&ctx).connect(&address)?;
+    client.send(vec!["a", "", "b"]).await?;
+    assert_eq!(client.next().await.unwrap()?, vec!["a", "", "b"].into());
+
+    // Requests of REQ clients still lose their delimiter.
+    let requester = request(&ctx).connect(&address)?;
+    let (reply, _) = requester
+        .send(vec!["x", "", "y"].into())
+        .await?
+        .recv()
+        .await?;
+    assert_eq!(reply, vec!["x", "", "y"].into());
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use zmq::{Context, SocketType};
 
-use tmq::{request, Multipart, Result};
+use std::{thread::spawn, time::Duration};
+use tmq::{request, request_client, Multipart, Result, TmqError};
 use utils::{generate_tcp_address, msg, sync_echo};
 
 mod utils;
@@ -53,3 +54,95 @@ async fn request_hammer() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn client_requests() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut client = request_client(&ctx).connect(&address)?;
+
+    let count = 100;
+    let echo = sync_echo(address, SocketType::REP, count);
+
+    for i in 0..count {
+        let m1 = format!("Msg #{}", i);
+        let message = Multipart::from(vec![msg(m1.as_bytes())]);
+        let reply = client.request(message).await?;
+        assert_eq!(reply, vec![msg(m1.as_bytes())].into());
+    }
+
+    echo.join().unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_recovers_after_timeout() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut client = request_client(&ctx).connect(&address)?;
+
+    // A ROUTER server which ignores the first request and echoes the late reply after the second.
+    let server_address = address.clone();
+    let server = spawn(move || {
+        let socket = Context::new().socket(SocketType::ROUTER).unwrap();
+        socket.bind(&server_address).unwrap();
+
+        let first = socket.recv_multipart(0).unwrap();
+        let second = socket.recv_multipart(0).unwrap();
+        socket.send_multipart(first, 0).unwrap();
+        socket.send_multipart(second, 0).unwrap();
+    });
+
+    let result = client
+        .request_timeout(vec!["first"].into(), Duration::from_millis(100))
+        .await;
+    assert!(matches!(result, Err(TmqError::Timeout)));
+
+    // The stale reply to the first request is discarded thanks to ZMQ_REQ_CORRELATE.
+    let reply = client.request(vec!["second"].into()).await?;
+    assert_eq!(reply, vec!["second"].into());
+
+    server.join().unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_share_deadline() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut client = request_client(&ctx).connect(&address)?;
+
+    // A ROUTER server which answers the first and the third request, but not the second.
+    let server_address = address.clone();
+    let server = spawn(move || {
+        let socket = Context::new().socket(SocketType::ROUTER).unwrap();
+        socket.bind(&server_address).unwrap();
+
+        let first = socket.recv_multipart(0).unwrap();
+        socket.send_multipart(first, 0).unwrap();
+        socket.recv_multipart(0).unwrap();
+        let third = socket.recv_multipart(0).unwrap();
+        socket.send_multipart(third, 0).unwrap();
+    });
+
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+    let reply = client
+        .request_deadline(vec!["first"].into(), deadline)
+        .await?;
+    assert_eq!(reply, vec!["first"].into());
+
+    let result = client
+        .request_deadline(vec!["second"].into(), deadline)
+        .await;
+    assert!(matches!(result, Err(TmqError::Timeout)));
+    assert!(tokio::time::Instant::now() >= deadline);
+
+    let reply = client.request(vec!["third"].into()).await?;
+    assert_eq!(reply, vec!["third"].into());
+
+    server.join().unwrap();
+
+    Ok(())
+}
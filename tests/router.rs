@@ -1,7 +1,11 @@
 use futures::{SinkExt, StreamExt};
 use zmq::{Context, SocketType};
 
-use tmq::{dealer, router, Multipart, Result};
+use tmq::{
+    dealer, request_client, router,
+    router::{PeerEvent, PeerId, Router, NOTIFY_CONNECT, NOTIFY_DISCONNECT},
+    Multipart, Result, TmqError,
+};
 
 use futures::Stream;
 use std::thread::{spawn, JoinHandle};
@@ -121,7 +125,7 @@ async fn echo() -> Result<()> {
                     msg,
                     response
                         .iter()
-                        .map(|i| std::str::from_utf8(&*i).unwrap())
+                        .map(|i| std::str::from_utf8(i).unwrap())
                         .collect::<Vec<&str>>()
                 );
             }
@@ -166,7 +170,7 @@ async fn proxy() -> Result<()> {
                         msg,
                         response
                             .iter()
-                            .map(|i| std::str::from_utf8(&*i).unwrap())
+                            .map(|i| std::str::from_utf8(i).unwrap())
                             .collect::<Vec<&str>>()
                     );
                 }
@@ -225,3 +229,247 @@ async fn proxy() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn routed_dealer_and_req_peers() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = router(&ctx)
+        .bind(&address)?
+        .routed()
+        .delimited_envelopes(true);
+
+    let mut dealer_peer = dealer(&ctx).set_identity(b"dealer").connect(&address)?;
+    let mut req_peer = request_client(&ctx).connect(&address)?;
+    let req_task = tokio::spawn(async move { req_peer.request(vec!["from req"].into()).await });
+
+    dealer_peer.send(vec!["from dealer"]).await?;
+    for _ in 0..2 {
+        let (peer, msg) = sock.next().await.unwrap()?;
+        // Identities and REQ delimiters are not part of the message.
+        assert_eq!(msg.len(), 1);
+        if peer == PeerId::from(&b"dealer"[..]) {
+            assert_eq!(msg, vec!["from dealer"].into());
+        } else {
+            assert_eq!(msg, vec!["from req"].into());
+        }
+        sock.send((peer, vec!["reply"].into())).await?;
+    }
+
+    assert_eq!(dealer_peer.next().await.unwrap()?, vec!["reply"].into());
+    assert_eq!(req_task.await.unwrap()?, vec!["reply"].into());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routed_envelopes_of_pipelined_requests() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = router(&ctx)
+        .bind(&address)?
+        .routed()
+        .delimited_envelopes(true);
+
+    // A DEALER peer can have several requests with different envelopes in flight.
+    let mut peer = dealer(&ctx).connect(&address)?;
+    peer.send(vec!["first", "", "a"]).await?;
+    peer.send(vec!["second", "", "b"]).await?;
+
+    let mut requests = vec![];
+    for _ in 0..2 {
+        requests.push(sock.next().await.unwrap()?);
+    }
+    assert_eq!(requests[0].1, vec!["a"].into());
+    assert_eq!(requests[1].1, vec!["b"].into());
+    for (id, _) in requests {
+        sock.send((id, vec!["reply"].into())).await?;
+    }
+
+    assert_eq!(
+        peer.next().await.unwrap()?,
+        vec!["first", "", "reply"].into()
+    );
+    assert_eq!(
+        peer.next().await.unwrap()?,
+        vec!["second", "", "reply"].into()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn buffered_routed() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let sock = router(&ctx).bind(&address)?.buffered(16);
+    assert_eq!(sock.batch_size(), 16);
+    sock.set_router_mandatory(true)?;
+    let mut sock = sock.routed();
+
+    let mut peer = dealer(&ctx).connect(&address)?;
+    for i in 0..4 {
+        peer.send(vec![format!("request {}", i).as_str()]).await?;
+    }
+    for i in 0..4 {
+        let (id, msg) = sock.next().await.unwrap()?;
+        assert_eq!(msg, vec![format!("request {}", i).as_str()].into());
+        sock.send((id, vec![format!("reply {}", i).as_str()].into()))
+            .await?;
+    }
+    for i in 0..4 {
+        assert_eq!(
+            peer.next().await.unwrap()?,
+            vec![format!("reply {}", i).as_str()].into()
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routed_keeps_empty_frames() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = router(&ctx).bind(&address)?.routed();
+
+    let mut peer = dealer(&ctx).connect(&address)?;
+    peer.send(vec!["a", "", "b"]).await?;
+    let (id, msg) = sock.next().await.unwrap()?;
+    assert_eq!(msg, vec!["a", "", "b"].into());
+
+    sock.send((id, msg)).await?;
+    assert_eq!(peer.next().await.unwrap()?, vec!["a", "", "b"].into());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routed_host_unreachable() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = router(&ctx)
+        .set_router_mandatory(true)
+        .set_router_handover(true)
+        .bind(&address)?
+        .routed();
+
+    // With ROUTER_MANDATORY, the socket is only writable once a peer is connected.
+    let mut peer = dealer(&ctx).set_identity(b"known").connect(&address)?;
+    peer.send(vec!["ping"]).await?;
+    let (id, msg) = sock.next().await.unwrap()?;
+    assert_eq!(id.as_bytes(), b"known");
+
+    let unknown = PeerId::from(b"unknown".to_vec());
+    match sock.send((unknown.clone(), vec!["hello"].into())).await {
+        Err(TmqError::HostUnreachable(peer)) => assert_eq!(peer, unknown),
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    // The socket is still usable afterwards.
+    sock.send((id, msg)).await?;
+    assert_eq!(peer.next().await.unwrap()?, vec!["ping"].into());
+
+    Ok(())
+}
+
+/// Binds a ROUTER with `ZMQ_ROUTER_NOTIFY`, or returns `None` if libzmq lacks the DRAFT API.
+fn notifying_router(ctx: &Context, address: &str, notify: i32) -> Result<Option<Router>> {
+    match router(ctx).set_router_notify(notify).bind(address) {
+        Ok(router) => Ok(Some(router)),
+        Err(TmqError::Zmq(zmq::Error::EINVAL)) => {
+            eprintln!("Skipping test, libzmq was built without the DRAFT API");
+            // Without notifications, peers can't be tracked.
+            let router = router(ctx).bind(&generate_tcp_address())?;
+            assert!(matches!(
+                router.track_peers(),
+                Err(TmqError::Zmq(zmq::Error::EINVAL))
+            ));
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[tokio::test]
+async fn track_peers() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let router = match notifying_router(&ctx, &address, NOTIFY_CONNECT | NOTIFY_DISCONNECT)? {
+        Some(router) => router,
+        None => return Ok(()),
+    };
+    let mut tracker = router.track_peers()?;
+    let id = PeerId::from(&b"peer"[..]);
+
+    let mut peer = dealer(&ctx).set_identity(b"peer").connect(&address)?;
+    assert_eq!(
+        tracker.next().await.unwrap()?,
+        PeerEvent::Connected(id.clone())
+    );
+    assert!(tracker.is_connected(&id));
+
+    peer.send(vec!["hello"]).await?;
+    assert_eq!(
+        tracker.next().await.unwrap()?,
+        PeerEvent::Message(id.clone(), vec!["hello"].into())
+    );
+    tracker.send(vec!["peer", "world"]).await?;
+    assert_eq!(peer.next().await.unwrap()?, vec!["world"].into());
+
+    drop(peer);
+    assert_eq!(
+        tracker.next().await.unwrap()?,
+        PeerEvent::Disconnected(id.clone())
+    );
+    assert!(tracker.peers().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn track_peers_with_disconnect_notifications() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let router = match notifying_router(&ctx, &address, NOTIFY_DISCONNECT)? {
+        Some(router) => router,
+        None => return Ok(()),
+    };
+    let mut tracker = router.track_peers()?;
+    let id = PeerId::from(&b"peer"[..]);
+
+    // The peer is known from its first message.
+    let mut peer = dealer(&ctx).set_identity(b"peer").connect(&address)?;
+    peer.send(vec!["hello"]).await?;
+    assert_eq!(
+        tracker.next().await.unwrap()?,
+        PeerEvent::Connected(id.clone())
+    );
+    assert_eq!(
+        tracker.next().await.unwrap()?,
+        PeerEvent::Message(id.clone(), vec!["hello"].into())
+    );
+
+    drop(peer);
+    assert_eq!(tracker.next().await.unwrap()?, PeerEvent::Disconnected(id));
+    assert!(tracker.peers().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn track_peers_requires_disconnect_notifications() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let router = match notifying_router(&ctx, &address, NOTIFY_CONNECT)? {
+        Some(router) => router,
+        None => return Ok(()),
+    };
+    // Disconnected peers would never be removed.
+    assert!(matches!(
+        router.track_peers(),
+        Err(TmqError::Zmq(zmq::Error::EINVAL))
+    ));
+
+    Ok(())
+}
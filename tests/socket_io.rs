@@ -0,0 +1,98 @@
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use tmq::{dealer, pair, pull, push, Context, Multipart, Result};
+
+fn test_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[tokio::test]
+async fn pair_roundtrip() -> Result<()> {
+    let address = "inproc://io-pair-roundtrip";
+    let ctx = Context::new();
+    let mut receiver = pair(&ctx).bind(address)?.into_io();
+    let mut sender = pair(&ctx).connect(address)?.into_io().with_frame_size(1000);
+
+    let data = test_data(1_000_000);
+    let send = async {
+        tokio::io::copy(&mut &data[..], &mut sender).await?;
+        sender.shutdown().await
+    };
+    let mut received = Vec::new();
+    let (sent, read) = tokio::join!(send, receiver.read_to_end(&mut received));
+    sent?;
+    assert_eq!(read?, data.len());
+    assert_eq!(received, data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn push_chunks_writes() -> Result<()> {
+    let address = "inproc://io-push-chunks-writes";
+    let ctx = Context::new();
+    let mut receiver = pull(&ctx).bind(address)?;
+    let mut sender = push(&ctx).connect(address)?.into_io().with_frame_size(4);
+
+    sender.write_all(b"hello world").await?;
+    sender.shutdown().await?;
+
+    for expected in [&b"hell"[..], b"o wo", b"rld", b""] {
+        let multipart = receiver.next().await.unwrap()?;
+        assert_eq!(multipart, Multipart::from(vec![expected]));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn pull_joins_frames() -> Result<()> {
+    let address = "inproc://io-pull-joins-frames";
+    let ctx = Context::new();
+    let mut receiver = pull(&ctx).bind(address)?.into_io();
+    let mut sender = push(&ctx).connect(address)?;
+
+    sender.send(vec!["ab", "cd"]).await?;
+    sender.send(vec!["", "ef"]).await?;
+    sender.send(vec![""]).await?;
+
+    let mut received = String::new();
+    receiver.read_to_string(&mut received).await?;
+    assert_eq!(received, "abcdef");
+    assert_eq!(receiver.read(&mut [0; 16]).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dealer_echo() -> Result<()> {
+    let address = "inproc://io-dealer-echo";
+    let ctx = Context::new();
+    let echo = dealer(&ctx).bind(address)?.into_io();
+    let mut client = dealer(&ctx)
+        .connect(address)?
+        .into_io()
+        .with_frame_size(4096);
+
+    let echo = tokio::spawn(async move {
+        let (mut read, mut write) = tokio::io::split(echo);
+        tokio::io::copy(&mut read, &mut write).await?;
+        write.shutdown().await
+    });
+
+    let data = test_data(100_000);
+    let (mut read, mut write) = tokio::io::split(&mut client);
+    let send = async {
+        write.write_all(&data).await?;
+        write.shutdown().await
+    };
+    let mut received = Vec::new();
+    let (sent, read) = tokio::join!(send, read.read_to_end(&mut received));
+    sent?;
+    read?;
+    assert_eq!(received, data);
+
+    echo.await.unwrap()?;
+    Ok(())
+}
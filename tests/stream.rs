@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use tmq::{stream, stream::StreamEvent, Context, Result};
+use utils::generate_tcp_address;
+
+mod utils;
+
+fn tcp_address(address: &str) -> &str {
+    address.trim_start_matches("tcp://")
+}
+
+#[tokio::test]
+async fn raw_stream_events() -> Result<()> {
+    let address = generate_tcp_address();
+    let mut socket = stream(&Context::new()).bind(&address)?;
+
+    let mut client = TcpStream::connect(tcp_address(&address)).await?;
+    let peer = match socket.next().await.unwrap()? {
+        StreamEvent::Connected(peer) => peer,
+        event => panic!("Unexpected event {:?}", event),
+    };
+    assert!(socket.is_connected(&peer));
+
+    client.write_all(b"hello").await?;
+    let mut received = Vec::new();
+    while received.len() < 5 {
+        match socket.next().await.unwrap()? {
+            StreamEvent::Data(from, data) => {
+                assert_eq!(from, peer);
+                received.extend_from_slice(&data);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+    assert_eq!(received, b"hello");
+
+    socket
+        .send((peer.clone(), Bytes::from_static(b"world")))
+        .await?;
+    let mut buf = [0; 5];
+    client.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"world");
+
+    drop(client);
+    assert_eq!(
+        socket.next().await.unwrap()?,
+        StreamEvent::Disconnected(peer.clone())
+    );
+    assert!(!socket.is_connected(&peer));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn raw_stream_connect_and_close() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = format!("tcp://{}", listener.local_addr()?);
+    let ctx = Context::new();
+    let mut socket = stream(&ctx).bind(&generate_tcp_address())?;
+
+    let peer = socket.connect_peer(&address)?;
+    assert!(ctx.open_sockets()[0].endpoints().contains(&address));
+    let (mut server, _) = listener.accept().await?;
+    assert_eq!(
+        socket.next().await.unwrap()?,
+        StreamEvent::Connected(peer.clone())
+    );
+
+    socket
+        .send((peer.clone(), Bytes::from_static(b"ping")))
+        .await?;
+    let mut buf = [0; 4];
+    server.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"ping");
+
+    // Empty data closes the connection, once the socket is polled.
+    socket.send((peer.clone(), Bytes::new())).await?;
+    tokio::select! {
+        event = socket.next() => panic!("Unexpected event {:?}", event),
+        read = server.read(&mut buf) => assert_eq!(read?, 0),
+    }
+    assert!(!socket.is_connected(&peer));
+    tokio::select! {
+        event = socket.next() => panic!("Unexpected event {:?}", event),
+        _ = listener.accept() => panic!("Unexpected reconnect"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_echo() -> Result<()> {
+    let address = generate_tcp_address();
+    let mut connections = stream(&Context::new()).bind(&address)?.into_connections();
+
+    let echo = tokio::spawn(async move {
+        let connection = connections.next().await.unwrap();
+        let (mut read, mut write) = tokio::io::split(connection);
+        // Ends once the client has disconnected.
+        tokio::io::copy(&mut read, &mut write).await
+    });
+
+    let mut client = TcpStream::connect(tcp_address(&address)).await?;
+    let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+    let (mut read, mut write) = client.split();
+    let mut received = vec![0; data.len()];
+    let (sent, read) = tokio::join!(write.write_all(&data), read.read_exact(&mut received));
+    sent?;
+    read?;
+    assert_eq!(received, data);
+
+    drop(client);
+    assert_eq!(echo.await.unwrap()?, data.len() as u64);
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_write_fails_after_disconnect() -> Result<()> {
+    let address = generate_tcp_address();
+    let mut connections = stream(&Context::new()).bind(&address)?.into_connections();
+
+    let client = TcpStream::connect(tcp_address(&address)).await?;
+    let mut connection = connections.next().await.unwrap();
+    drop(client);
+
+    let mut buf = [0; 1];
+    assert_eq!(connection.read(&mut buf).await?, 0);
+    let error = connection.write_all(b"lost").await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_holds_back_peer() -> Result<()> {
+    let address = generate_tcp_address();
+    let mut connections = stream(&Context::new()).bind(&address)?.into_connections();
+
+    let mut client = TcpStream::connect(tcp_address(&address)).await?;
+    let mut connection = connections.next().await.unwrap();
+    let data: Vec<u8> = (0..64 * 1024 * 1024u32).map(|i| i as u8).collect();
+    let sent = data.clone();
+    let mut writer = tokio::spawn(async move { client.write_all(&sent).await });
+
+    // The socket stops reading while the connection isn't read.
+    tokio::select! {
+        _ = &mut writer => panic!("Unexpected end of write"),
+        _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+    }
+
+    let mut received = vec![0; data.len()];
+    connection.read_exact(&mut received).await?;
+    assert!(received == data);
+    writer.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_lines() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = format!("tcp://{}", listener.local_addr()?);
+    let connections = stream(&Context::new())
+        .bind(&generate_tcp_address())?
+        .into_connections();
+
+    // Data written before the connection is established is queued.
+    let mut connection = connections.connect(&address).await?;
+    connection.write_all(b"first\nsecond\n").await?;
+    let (server, _) = listener.accept().await?;
+
+    let mut lines = BufReader::new(server).lines();
+    assert_eq!(lines.next_line().await?.as_deref(), Some("first"));
+    assert_eq!(lines.next_line().await?.as_deref(), Some("second"));
+
+    // Dropping the connection closes it.
+    drop(connection);
+    let closed = tokio::time::timeout(Duration::from_secs(5), lines.next_line()).await;
+    assert_eq!(closed.unwrap()?, None);
+
+    Ok(())
+}
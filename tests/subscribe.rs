@@ -38,3 +38,76 @@ async fn receive_single_message() -> Result<()> {
 
     panic!("Didn't receive published message");
 }
+
+#[tokio::test]
+async fn receive_buffered() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let topic: &[u8] = b"topic";
+
+    let mut sub_sock = subscribe(&ctx)
+        .connect(&address)?
+        .subscribe(topic)?
+        .buffered(64);
+    let pub_sock = Context::new().socket(SocketType::PUB).unwrap();
+    pub_sock.bind(&address).unwrap();
+
+    // Wait until the subscription has reached the publisher.
+    let mut connected = false;
+    for _ in 0usize..5 {
+        pub_sock.send_multipart([topic, b"ping"], 0).unwrap();
+        if let Ok(Some(incoming)) = timeout(Duration::from_millis(100), sub_sock.next()).await {
+            assert_eq!(incoming?, vec![topic, b"ping"].into());
+            connected = true;
+            break;
+        }
+    }
+    assert!(connected, "Didn't receive published message");
+
+    for i in 0..500u32 {
+        pub_sock
+            .send_multipart([topic, &i.to_be_bytes()], 0)
+            .unwrap();
+    }
+    let mut expected = 0u32;
+    while expected < 500 {
+        let incoming = sub_sock.next().await.unwrap()?;
+        // Left over pings from the connection attempts.
+        if incoming[1][..] == b"ping"[..] {
+            continue;
+        }
+        assert_eq!(incoming, vec![topic, &expected.to_be_bytes()].into());
+        expected += 1;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_after_buffered() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let topic: &[u8] = b"topic";
+
+    let mut sub_sock = subscribe(&ctx)
+        .connect(&address)?
+        .subscribe(b"other")?
+        .buffered(64);
+    sub_sock.subscribe(topic)?;
+    sub_sock.unsubscribe(b"other")?;
+    let pub_sock = Context::new().socket(SocketType::PUB).unwrap();
+    pub_sock.bind(&address).unwrap();
+
+    let mut received = false;
+    for _ in 0usize..5 {
+        pub_sock.send_multipart([topic, b"ping"], 0).unwrap();
+        if let Ok(Some(incoming)) = timeout(Duration::from_millis(100), sub_sock.next()).await {
+            assert_eq!(incoming?, vec![topic, b"ping"].into());
+            received = true;
+            break;
+        }
+    }
+    assert!(received, "Didn't receive published message");
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use futures::{SinkExt, StreamExt};
+use zmq::{Context, SocketType};
+
+use std::time::{Duration, Instant};
+use tmq::{dealer, pull, push, Result, SocketTimeoutExt, TmqError};
+use utils::{generate_tcp_address, sync_send_multiparts};
+
+mod utils;
+
+#[tokio::test]
+async fn recv_timeout_expires() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = pull(&ctx).bind(&address)?;
+
+    let start = Instant::now();
+    let result = sock.recv_timeout(Duration::from_millis(100)).await;
+    assert!(matches!(result, Err(TmqError::Timeout)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recv_timeout_receives() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = pull(&ctx).bind(&address)?;
+
+    let thread = sync_send_multiparts(address, SocketType::PUSH, vec![vec!["hello", "world"]]);
+    let multipart = sock.recv_timeout(Duration::from_secs(5)).await?;
+    assert_eq!(multipart, vec!["hello", "world"].into());
+    thread.join().unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recv_timeout_defaults_to_rcvtimeo() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = pull(&ctx).set_rcvtimeo(100).bind(&address)?;
+
+    let result = tokio::time::timeout(Duration::from_secs(5), sock.recv_timeout(None)).await;
+    assert!(matches!(result, Ok(Err(TmqError::Timeout))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_timeout_expires() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    // Without a connected peer, PUSH sockets can't send.
+    let mut sock = push(&ctx).set_sndtimeo(100).bind(&address)?;
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        sock.send_timeout(vec!["hello"], None),
+    )
+    .await;
+    assert!(matches!(result, Ok(Err(TmqError::Timeout))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn split_halves_use_socket_timeouts() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    // Without a connected peer, DEALER sockets can neither receive nor send.
+    let (mut write, mut read) = dealer(&ctx)
+        .set_rcvtimeo(100)
+        .set_sndtimeo(100)
+        .bind(&address)?
+        .into_split();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), read.recv_timeout(None)).await;
+    assert!(matches!(result, Ok(Err(TmqError::Timeout))));
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        write.send_timeout(vec!["hello"].into(), None),
+    )
+    .await;
+    assert!(matches!(result, Ok(Err(TmqError::Timeout))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deadline_applies_to_all_operations() -> Result<()> {
+    let address = "inproc://deadline-applies-to-all-operations";
+    let ctx = Context::new();
+    let receiver = pull(&ctx).bind(address)?;
+    let sender = push(&ctx).connect(address)?;
+
+    let deadline = Instant::now() + Duration::from_millis(200);
+    let mut receiver = receiver.with_deadline(deadline);
+    let mut sender = sender.with_deadline(deadline);
+
+    sender.send(vec!["hello"]).await?;
+    assert_eq!(receiver.next().await.unwrap()?, vec!["hello"].into());
+
+    assert!(matches!(
+        receiver.next().await.unwrap(),
+        Err(TmqError::Timeout)
+    ));
+    assert!(Instant::now() >= deadline);
+
+    Ok(())
+}
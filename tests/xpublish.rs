@@ -0,0 +1,147 @@
+use futures::{SinkExt, StreamExt};
+use zmq::{Context, SocketType};
+
+use std::thread::spawn;
+use tmq::{xpublish, xpublish::SubscriptionEvent, Message, Result};
+use utils::generate_tcp_address;
+
+mod utils;
+
+#[tokio::test]
+async fn receive_subscription_events() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = xpublish(&ctx).bind(&address)?;
+
+    let thread = spawn(move || {
+        let socket = Context::new().socket(SocketType::SUB).unwrap();
+        socket.connect(&address).unwrap();
+        socket.set_subscribe(b"topic").unwrap();
+        socket.set_unsubscribe(b"topic").unwrap();
+        // keep the socket alive until the unsubscription has been flushed
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    });
+
+    assert_eq!(
+        sock.next().await.unwrap()?,
+        SubscriptionEvent::Subscribe(b"topic".to_vec())
+    );
+    assert_eq!(
+        sock.next().await.unwrap()?,
+        SubscriptionEvent::Unsubscribe(b"topic".to_vec())
+    );
+
+    thread.join().unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn publish_to_subscriber() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = xpublish(&ctx).bind(&address)?;
+
+    let thread = spawn(move || {
+        let socket = Context::new().socket(SocketType::SUB).unwrap();
+        socket.connect(&address).unwrap();
+        socket.set_subscribe(b"topic").unwrap();
+        let received = socket.recv_multipart(0).unwrap();
+        assert_eq!(received, vec![b"topic".to_vec(), b"hello".to_vec()]);
+    });
+
+    // Once the subscription arrives, the subscriber is guaranteed to receive the message.
+    let event = sock.next().await.unwrap()?;
+    assert_eq!(event.topic(), b"topic");
+    sock.send(vec!["topic", "hello"]).await?;
+
+    thread.join().unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verbose_duplicate_subscriptions() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = xpublish(&ctx)
+        .set_xpub_verbose(true)
+        .set_xpub_nodrop(true)
+        .bind(&address)?;
+
+    let threads = (0..2)
+        .map(|_| {
+            let address = address.clone();
+            spawn(move || {
+                let socket = Context::new().socket(SocketType::SUB).unwrap();
+                socket.connect(&address).unwrap();
+                socket.set_subscribe(b"topic").unwrap();
+                socket.recv_multipart(0).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Without ZMQ_XPUB_VERBOSE, the second subscription wouldn't be passed through.
+    for _ in 0..2 {
+        assert_eq!(
+            sock.next().await.unwrap()?,
+            SubscriptionEvent::Subscribe(b"topic".to_vec())
+        );
+    }
+    sock.send(vec!["topic"]).await?;
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    Ok(())
+}
+
+#[test]
+fn subscription_event_frames() {
+    let subscribe = Message::from(SubscriptionEvent::Subscribe(b"abc".to_vec()));
+    assert_eq!(&subscribe[..], b"\x01abc");
+    assert_eq!(
+        SubscriptionEvent::from_message(&subscribe),
+        Some(SubscriptionEvent::Subscribe(b"abc".to_vec()))
+    );
+
+    let unsubscribe = Message::from(SubscriptionEvent::Unsubscribe(b"abc".to_vec()));
+    assert_eq!(&unsubscribe[..], b"\x00abc");
+    assert_eq!(
+        SubscriptionEvent::from_message(&unsubscribe),
+        Some(SubscriptionEvent::Unsubscribe(b"abc".to_vec()))
+    );
+
+    assert_eq!(SubscriptionEvent::from_message(&Message::from("abc")), None);
+    assert_eq!(SubscriptionEvent::from_message(&Message::new()), None);
+}
+
+#[tokio::test]
+async fn buffered_receive_subscription_events() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let mut sock = xpublish(&ctx).bind(&address)?.buffered(8);
+
+    let thread = spawn(move || {
+        let socket = Context::new().socket(SocketType::SUB).unwrap();
+        socket.connect(&address).unwrap();
+        for topic in [&b"a"[..], b"b", b"c"] {
+            socket.set_subscribe(topic).unwrap();
+        }
+        let received = socket.recv_multipart(0).unwrap();
+        assert_eq!(received, vec![b"c".to_vec(), b"hello".to_vec()]);
+    });
+
+    for topic in [&b"a"[..], b"b", b"c"] {
+        assert_eq!(
+            sock.next().await.unwrap()?,
+            SubscriptionEvent::Subscribe(topic.to_vec())
+        );
+    }
+    sock.send(vec!["c", "hello"]).await?;
+
+    thread.join().unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use futures::{SinkExt, StreamExt};
+use tmq::{xpublish, xpublish::SubscriptionEvent, xsubscribe, Multipart, Result};
+use zmq::{Context, SocketType};
+
+use std::time::Duration;
+use tokio::time::timeout;
+use utils::generate_tcp_address;
+
+mod utils;
+
+#[tokio::test]
+async fn receive_single_message() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+    let topic: &[u8] = b"topic2";
+
+    let mut xsub_sock = xsubscribe(&ctx).connect(&address)?;
+    xsub_sock.subscribe(topic).await?;
+    let data = vec![topic, b"hello", b"world"];
+    let pub_sock = Context::new().socket(SocketType::PUB).unwrap();
+    pub_sock.bind(&address).unwrap();
+
+    // XSUB sockets don't know when they're connected by zmq design.
+    // Retry a few times in case the subscription arrives late.
+    for _ in 0usize..5 {
+        pub_sock.send_multipart(&data, 0).unwrap();
+        if let Ok(Some(Ok(incoming))) = timeout(Duration::from_millis(100), xsub_sock.next()).await
+        {
+            assert_eq!(
+                incoming,
+                data.into_iter()
+                    .map(tmq::Message::from)
+                    .collect::<Multipart>()
+            );
+            return Ok(());
+        }
+    }
+
+    panic!("Didn't receive published message");
+}
+
+#[tokio::test]
+async fn subscription_frames() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+
+    let mut xpub_sock = xpublish(&ctx).bind(&address)?;
+    let mut xsub_sock = xsubscribe(&ctx).connect(&address)?;
+
+    xsub_sock.subscribe(b"topic").await?;
+    assert_eq!(
+        xpub_sock.next().await.unwrap()?,
+        SubscriptionEvent::Subscribe(b"topic".to_vec())
+    );
+
+    xpub_sock.send(vec!["topic", "hello"]).await?;
+    let message = xsub_sock.next().await.unwrap()?;
+    assert_eq!(message, vec!["topic", "hello"].into());
+
+    xsub_sock.unsubscribe(b"topic").await?;
+    assert_eq!(
+        xpub_sock.next().await.unwrap()?,
+        SubscriptionEvent::Unsubscribe(b"topic".to_vec())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn forward_raw_subscription() -> Result<()> {
+    let address = generate_tcp_address();
+    let ctx = Context::new();
+
+    let mut xpub_sock = xpublish(&ctx).bind(&address)?;
+    let mut xsub_sock = xsubscribe(&ctx).connect(&address)?;
+
+    let event = SubscriptionEvent::Subscribe(b"forwarded".to_vec());
+    xsub_sock.send(tmq::Message::from(event.clone())).await?;
+    assert_eq!(xpub_sock.next().await.unwrap()?, event);
+
+    Ok(())
+}